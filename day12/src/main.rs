@@ -1,79 +1,481 @@
-use std::fs::File;
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap};
 
 type CavesGraph = HashMap<String,Vec<String>>;
-type CavesPath = Vec<String>;
-type CaveInvalidFn = fn(&String, &CavesPath) -> bool;
+
+#[derive(Clone, Debug)]
+struct VisitPolicy {
+    start: String,
+    end: String,
+    small_max_visits: usize,
+    max_visits: HashMap<String, usize>,
+    extra_revisits: usize,
+    required: Vec<String>,
+    forbidden: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+struct PolicyError {
+    what: String,
+}
+
+#[derive(Clone, Debug)]
+struct Args {
+    filename: String,
+    policy: Option<VisitPolicy>,
+    dot_filename: Option<String>,
+    dot_overlay: DotOverlay,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum DotOverlay {
+    None,
+    Path(Vec<String>),
+    EdgeUsage,
+}
 
 fn main() {
-    let caves_graph = parse_caves_graph("input.txt");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let caves_graph = parse_caves_graph(&args.filename);
+
+    if let Some(dot_filename) = &args.dot_filename {
+        let policy = args.policy.clone().unwrap_or_default();
+        if let Err(e) = export_dot(&caves_graph, &policy, &args.dot_overlay, dot_filename) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        println!("Caves graph written to {}", dot_filename);
+    }
+
+    if let Some(policy) = &args.policy {
+        match solve(&caves_graph, policy) {
+            Ok(paths_count) => println!("Custom policy: number of paths={}", paths_count),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-    let paths_count = solve(&caves_graph, is_cave_small_and_visited);
+    let paths_count = solve(&caves_graph, &VisitPolicy::default()).unwrap();
     println!("Part 1: number of paths={}", paths_count);
 
-    let paths_count = solve(&caves_graph, is_cave_ending_or_small_and_visited_twice);
+    let policy = VisitPolicy { extra_revisits: 1, ..VisitPolicy::default() };
+    let paths_count = solve(&caves_graph, &policy).unwrap();
     println!("Part 2: number of paths={}", paths_count);
 }
 
-fn solve(caves_graph: &CavesGraph, is_cave_invalid: CaveInvalidFn) -> usize {
-    let mut partial_paths = VecDeque::from([vec!["start".to_string()]]);
-    let mut completed_paths = Vec::new();
+fn solve(caves_graph: &CavesGraph, policy: &VisitPolicy) -> Result<usize, PolicyError> {
+    policy.validate(caves_graph)?;
+
+    let mut paths_count = 0;
+    for_each_path(caves_graph, policy, |_| paths_count += 1);
+    Ok(paths_count)
+}
+
+/// Depth first enumeration of every path allowed by the policy. The policy must
+/// have been validated against the graph, otherwise this might never finish.
+fn for_each_path<'a, F>(caves_graph: &'a CavesGraph, policy: &VisitPolicy, mut on_path: F)
+where
+    F: FnMut(&[&'a str])
+{
+    let (start, _) = caves_graph.get_key_value(&policy.start).unwrap();
+    let mut path = vec![start.as_str()];
+    let mut visits = HashMap::from([(start.as_str(), 1)]);
+    walk(caves_graph, policy, &mut path, &mut visits, policy.extra_revisits, &mut on_path);
+}
+
+fn walk<'a, F>(
+    caves_graph: &'a CavesGraph,
+    policy: &VisitPolicy,
+    path: &mut Vec<&'a str>,
+    visits: &mut HashMap<&'a str, usize>,
+    extra_revisits: usize,
+    on_path: &mut F,
+)
+where
+    F: FnMut(&[&'a str])
+{
+    let last_cave = *path.last().unwrap();
+    if last_cave == policy.end {
+        if policy.required.iter().all(|c| visits.contains_key(c.as_str())) {
+            on_path(path);
+        }
+        return;
+    }
+
+    for cave in caves_graph.get(last_cave).into_iter().flatten() {
+        let cave = cave.as_str();
+        if policy.forbidden.iter().any(|c| c == cave) {
+            continue;
+        }
+
+        let visited = visits.get(cave).copied().unwrap_or(0);
+        let extra_revisits = match policy.max_visits_of(cave) {
+            Some(max) if visited >= max => {
+                if extra_revisits == 0 || !policy.can_revisit_extra(cave) {
+                    continue;
+                }
+                extra_revisits - 1
+            },
+            _ => extra_revisits,
+        };
+
+        path.push(cave);
+        *visits.entry(cave).or_insert(0) += 1;
+
+        walk(caves_graph, policy, path, visits, extra_revisits, on_path);
+
+        path.pop();
+        let count = visits.get_mut(cave).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            visits.remove(cave);
+        }
+    }
+}
+
+fn export_dot(
+    caves_graph: &CavesGraph,
+    policy: &VisitPolicy,
+    overlay: &DotOverlay,
+    filename: &str,
+) -> Result<(), PolicyError> {
+    let dot = caves_graph_to_dot(caves_graph, policy, overlay)?;
+    fs::write(filename, dot).map_err(|e| PolicyError::new(format!("Can't write {}: {}", filename, e)))
+}
 
-    while let Some(path) = partial_paths.pop_front() {
-        let last_cave = path.iter().last().unwrap();
-        let next_caves = caves_graph.get(last_cave).unwrap();
+/// Graphviz DOT representation of the caves graph. Big caves are drawn as
+/// boxes and small caves as ellipses, with start and end in bold. The overlay
+/// can highlight the edges of a given path, labeled with the steps that walk
+/// them, or set each edge's label and width by how many valid paths use it.
+fn caves_graph_to_dot(
+    caves_graph: &CavesGraph,
+    policy: &VisitPolicy,
+    overlay: &DotOverlay,
+) -> Result<String, PolicyError> {
+    let mut edge_labels: BTreeMap<(&str, &str), String> = BTreeMap::new();
+    let mut edge_widths: BTreeMap<(&str, &str), f64> = BTreeMap::new();
 
-        for cave in next_caves {
-            if is_cave_invalid(cave, &path) {
-                continue;
+    match overlay {
+        DotOverlay::None => (),
+        DotOverlay::Path(path) => {
+            for (step, caves) in path.windows(2).enumerate() {
+                let is_edge = caves_graph.get(&caves[0]).is_some_and(|next| next.contains(&caves[1]));
+                if !is_edge {
+                    return Err(PolicyError::new(
+                        format!("Invalid path: no connection '{}-{}'", caves[0], caves[1])
+                    ));
+                }
+                let label = edge_labels.entry(edge_key(&caves[0], &caves[1])).or_default();
+                if !label.is_empty() {
+                    label.push(',');
+                }
+                label.push_str(&(step + 1).to_string());
+            }
+            for edge in edge_labels.keys() {
+                edge_widths.insert(*edge, 3.0);
+            }
+        },
+        DotOverlay::EdgeUsage => {
+            policy.validate(caves_graph)?;
+            let mut usage: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+            for_each_path(caves_graph, policy, |path| {
+                let mut edges: Vec<_> = path.windows(2).map(|c| edge_key(c[0], c[1])).collect();
+                edges.sort_unstable();
+                edges.dedup();
+                for edge in edges {
+                    *usage.entry(edge).or_insert(0) += 1;
+                }
+            });
+            let max = usage.values().copied().max().unwrap_or(1) as f64;
+            for (edge, count) in usage {
+                edge_labels.insert(edge, count.to_string());
+                edge_widths.insert(edge, 1.0 + 7.0 * count as f64 / max);
             }
+        },
+    }
+
+    let mut caves: Vec<&str> = caves_graph.keys().map(|c| c.as_str()).collect();
+    caves.sort_unstable();
+
+    let mut dot = String::from("graph caves {\n");
+    for cave in &caves {
+        let shape = if is_cave_small(cave) { "ellipse" } else { "box" };
+        let mut attrs = format!("shape={}", shape);
+        if !is_cave_small(cave) {
+            attrs.push_str(", style=filled, fillcolor=lightgrey");
+        }
+        if *cave == policy.start || *cave == policy.end {
+            attrs.push_str(", penwidth=3");
+        }
+        dot.push_str(&format!("    \"{}\" [{}];\n", cave, attrs));
+    }
 
-            let mut new_path = path.clone();
-            new_path.push(cave.clone());
+    let mut edges: Vec<(&str, &str)> = caves_graph.iter()
+        .flat_map(|(c0, next)| next.iter().map(move |c1| edge_key(c0, c1)))
+        .collect();
+    edges.sort_unstable();
+    edges.dedup();
 
-            if cave != "end" {
-                partial_paths.push_back(new_path);
-            } else {
-                completed_paths.push(new_path);
+    for edge in edges {
+        let mut attrs = Vec::new();
+        if let Some(label) = edge_labels.get(&edge) {
+            attrs.push(format!("label=\"{}\"", label));
+        }
+        if let Some(width) = edge_widths.get(&edge) {
+            attrs.push(format!("penwidth={:.1}", width));
+            if let DotOverlay::Path(_) = overlay {
+                attrs.push("color=red".to_string());
             }
         }
+        let attrs = if attrs.is_empty() { String::new() } else { format!(" [{}]", attrs.join(", ")) };
+        dot.push_str(&format!("    \"{}\" -- \"{}\"{};\n", edge.0, edge.1, attrs));
     }
+    dot.push_str("}\n");
+
+    Ok(dot)
+}
 
-    completed_paths.len()
+fn edge_key<'a>(cave0: &'a str, cave1: &'a str) -> (&'a str, &'a str) {
+    if cave0 <= cave1 { (cave0, cave1) } else { (cave1, cave0) }
 }
 
-fn is_cave_small(cave: &String) -> bool {
+fn is_cave_small(cave: &str) -> bool {
     cave.chars().next().unwrap().is_ascii_lowercase()
 }
 
-fn is_cave_small_and_visited(cave: &String, path: &CavesPath) -> bool {
-    is_cave_small(cave) && path.contains(cave)
+impl Default for VisitPolicy {
+    fn default() -> Self {
+        VisitPolicy {
+            start: "start".to_string(),
+            end: "end".to_string(),
+            small_max_visits: 1,
+            max_visits: HashMap::new(),
+            extra_revisits: 0,
+            required: Vec::new(),
+            forbidden: Vec::new(),
+        }
+    }
 }
 
-fn is_cave_ending_or_small_and_visited_twice(cave: &String, path: &CavesPath) -> bool {
-    if cave == "start" {
-        true
-    } else if !is_cave_small_and_visited(cave, path) {
-        false
+impl VisitPolicy {
+    /// Max number of visits to a cave, or None if unlimited
+    fn max_visits_of(&self, cave: &str) -> Option<usize> {
+        if let Some(max) = self.max_visits.get(cave) {
+            Some(*max)
+        } else if cave == self.start {
+            Some(1)
+        } else if is_cave_small(cave) {
+            Some(self.small_max_visits)
+        } else {
+            None
+        }
+    }
+
+    /// Only caves limited by `small_max_visits` can use the extra revisits,
+    /// an explicit max visits is a hard limit
+    fn can_revisit_extra(&self, cave: &str) -> bool {
+        cave != self.start && cave != self.end && is_cave_small(cave) && !self.max_visits.contains_key(cave)
+    }
+
+    fn validate(&self, caves_graph: &CavesGraph) -> Result<(), PolicyError> {
+        let check_exists = |cave: &str| {
+            match caves_graph.contains_key(cave) {
+                true => Ok(()),
+                false => Err(PolicyError::new(format!("Unknown cave '{}'", cave))),
+            }
+        };
+
+        check_exists(&self.start)?;
+        check_exists(&self.end)?;
+        if self.start == self.end {
+            return Err(PolicyError::new("Start and end caves must be different".to_string()));
+        }
+
+        for cave in self.max_visits.keys().chain(&self.required).chain(&self.forbidden) {
+            check_exists(cave)?;
+        }
+
+        for cave in &self.forbidden {
+            if *cave == self.start || *cave == self.end {
+                return Err(PolicyError::new(format!("Can't forbid start or end cave '{}'", cave)));
+            }
+            if self.required.contains(cave) {
+                return Err(PolicyError::new(format!("Cave '{}' is both required and forbidden", cave)));
+            }
+        }
+
+        for cave in &self.required {
+            if self.max_visits.get(cave) == Some(&0) {
+                return Err(PolicyError::new(format!("Required cave '{}' has max visits 0", cave)));
+            }
+        }
+
+        // Two connected caves that can be visited unlimited times would give
+        // infinite paths going back and forth between them
+        for (cave, next_caves) in caves_graph {
+            if self.is_unbounded(cave) {
+                if let Some(next) = next_caves.iter().find(|c| self.is_unbounded(c)) {
+                    return Err(PolicyError::new(
+                        format!("Infinite paths: '{}' and '{}' can be visited unlimited times", cave, next)
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_unbounded(&self, cave: &str) -> bool {
+        cave != self.end && !self.forbidden.iter().any(|c| c == cave) && self.max_visits_of(cave).is_none()
+    }
+}
+
+impl PolicyError {
+    fn new(what: String) -> Self {
+        PolicyError { what }
+    }
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.what)
+    }
+}
+
+/// Parse the command line. With no policy options, policy is None so the
+/// puzzle's 2 parts are solved. Options:
+///   --input FILE            caves graph file (default input.txt)
+///   --policy FILE           read options from a file, one "option value" per line
+///   --start CAVE, --end CAVE
+///   --small-max N           max visits to small caves (default 1, start is always 1)
+///   --max-visits CAVE=N     max visits to a given cave, not raised by --extra-revisits
+///   --extra-revisits K      extra visits allowed in total to already visited small caves
+///   --require CAVE, --forbid CAVE
+///   --dot FILE              export the caves graph in Graphviz DOT format
+///   --dot-overlay OVERLAY   "usage" (edges usage in all valid paths) or
+///                           "path=CAVE,CAVE,..." (highlight a path)
+fn parse_args(args: &[String]) -> Result<Args, PolicyError> {
+    let mut filename = "input.txt".to_string();
+    let mut policy = VisitPolicy::default();
+    let mut has_policy = false;
+    let mut dot_filename = None;
+    let mut dot_overlay = DotOverlay::None;
+
+    let mut args = args.iter();
+    while let Some(opt) = args.next() {
+        let value = args.next()
+            .ok_or_else(|| PolicyError::new(format!("Missing value for option '{}'", opt)))?;
+        match opt.as_str() {
+            "--input" => filename = value.clone(),
+            "--dot" => dot_filename = Some(value.clone()),
+            "--dot-overlay" => dot_overlay = parse_dot_overlay(value)?,
+            "--policy" => {
+                parse_policy_file(value, &mut policy)?;
+                has_policy = true;
+            },
+            _ => {
+                parse_policy_option(opt.trim_start_matches("--"), value, &mut policy)?;
+                has_policy = true;
+            },
+        }
+    }
+
+    if dot_overlay != DotOverlay::None && dot_filename.is_none() {
+        return Err(PolicyError::new("--dot-overlay requires --dot".to_string()));
+    }
+
+    Ok(Args {
+        filename,
+        policy: if has_policy { Some(policy) } else { None },
+        dot_filename,
+        dot_overlay,
+    })
+}
+
+fn parse_dot_overlay(value: &str) -> Result<DotOverlay, PolicyError> {
+    if value == "usage" {
+        Ok(DotOverlay::EdgeUsage)
+    } else if let Some(caves) = value.strip_prefix("path=") {
+        Ok(DotOverlay::Path(caves.split(',').map(|c| c.trim().to_string()).collect()))
     } else {
-        path_has_any_small_twice(path)
+        Err(PolicyError::new(format!("Invalid DOT overlay '{}'", value)))
     }
 }
 
-fn path_has_any_small_twice(path: &CavesPath) -> bool {
-    let mut smalls: Vec<&String> = path.iter().filter(|&c| is_cave_small(c)).collect();
-    smalls.sort_unstable();
-    smalls.windows(2).any(|caves| caves[0] == caves[1])
+fn parse_policy_file(filename: &str, policy: &mut VisitPolicy) -> Result<(), PolicyError> {
+    let f = File::open(filename)
+        .map_err(|e| PolicyError::new(format!("Can't open {}: {}", filename, e)))?;
+    let reader = BufReader::new(f);
+    parse_policy_lines(reader.lines().map(|l| l.unwrap()), policy)
+}
+
+fn parse_policy_lines<T>(lines: T, policy: &mut VisitPolicy) -> Result<(), PolicyError>
+where
+    T: Iterator<Item = String>
+{
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (opt, value) = line.split_once(char::is_whitespace)
+            .ok_or_else(|| PolicyError::new(format!("Invalid policy line '{}'", line)))?;
+        parse_policy_option(opt, value.trim(), policy)?;
+    }
+
+    Ok(())
+}
+
+fn parse_policy_option(opt: &str, value: &str, policy: &mut VisitPolicy) -> Result<(), PolicyError> {
+    let parse_num = |s: &str| {
+        s.parse::<usize>().map_err(|_| PolicyError::new(format!("Invalid number '{}' for '{}'", s, opt)))
+    };
+
+    match opt {
+        "start" => policy.start = value.to_string(),
+        "end" => policy.end = value.to_string(),
+        "small-max" => policy.small_max_visits = parse_num(value)?,
+        "extra-revisits" => policy.extra_revisits = parse_num(value)?,
+        "require" => policy.required.push(value.to_string()),
+        "forbid" => policy.forbidden.push(value.to_string()),
+        "max-visits" => {
+            let (cave, max) = value.split_once('=')
+                .ok_or_else(|| PolicyError::new(format!("Expected CAVE=N for '{}', got '{}'", opt, value)))?;
+            policy.max_visits.insert(cave.to_string(), parse_num(max)?);
+        },
+        _ => return Err(PolicyError::new(format!("Unknown option '{}'", opt))),
+    }
+
+    Ok(())
 }
 
 fn parse_caves_graph(filename: &str) -> CavesGraph {
-    let f = File::open(filename).expect(format!("Can't open {}", filename).as_str());
+    let f = File::open(filename).unwrap_or_else(|_| panic!("Can't open {}", filename));
     let reader = BufReader::new(f);
+    parse_caves_lines(reader.lines().map(|l| l.unwrap()))
+}
 
+fn parse_caves_lines<T>(lines: T) -> CavesGraph
+where
+    T: Iterator<Item = String>
+{
     let mut caves_graph = CavesGraph::new();
 
-    for line in reader.lines().map(|l| l.unwrap()) {
+    for line in lines {
         let caves: Vec<&str> = line.split('-').collect();
         assert_eq!(caves.len(), 2);
 
@@ -88,4 +490,129 @@ fn parse_caves_graph(filename: &str) -> CavesGraph {
     }
 
     caves_graph
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "start-A\nstart-b\nA-c\nA-b\nb-d\nA-end\nb-end";
+
+    fn example_graph() -> CavesGraph {
+        parse_caves_lines(EXAMPLE.lines().map(|l| l.to_string()))
+    }
+
+    #[test]
+    fn puzzle_parts() {
+        let graph = example_graph();
+        assert_eq!(solve(&graph, &VisitPolicy::default()).unwrap(), 10);
+        let policy = VisitPolicy { extra_revisits: 1, ..VisitPolicy::default() };
+        assert_eq!(solve(&graph, &policy).unwrap(), 36);
+    }
+
+    #[test]
+    fn required_and_forbidden() {
+        let graph = example_graph();
+        let policy = VisitPolicy { forbidden: vec!["A".to_string()], ..VisitPolicy::default() };
+        assert_eq!(solve(&graph, &policy).unwrap(), 1);
+
+        let policy = VisitPolicy { required: vec!["d".to_string()], ..VisitPolicy::default() };
+        assert_eq!(solve(&graph, &policy).unwrap(), 0);
+
+        let policy = VisitPolicy {
+            required: vec!["c".to_string()],
+            forbidden: vec!["c".to_string()],
+            ..VisitPolicy::default()
+        };
+        assert!(solve(&graph, &policy).is_err());
+    }
+
+    #[test]
+    fn infinite_paths() {
+        let mut graph = example_graph();
+        graph.get_mut("A").unwrap().push("B".to_string());
+        graph.insert("B".to_string(), vec!["A".to_string()]);
+        assert!(solve(&graph, &VisitPolicy::default()).is_err());
+    }
+
+    #[test]
+    fn dot_export() {
+        let graph = example_graph();
+        let policy = VisitPolicy::default();
+
+        let dot = caves_graph_to_dot(&graph, &policy, &DotOverlay::None).unwrap();
+        assert!(dot.contains("\"A\" [shape=box"));
+        assert!(dot.contains("\"b\" -- \"d\";"));
+
+        let dot = caves_graph_to_dot(&graph, &policy, &DotOverlay::EdgeUsage).unwrap();
+        assert!(dot.contains("\"b\" -- \"end\" [label=\"3\""));
+
+        let path = ["start", "A", "b", "A", "end"].map(|c| c.to_string()).to_vec();
+        let dot = caves_graph_to_dot(&graph, &policy, &DotOverlay::Path(path)).unwrap();
+        assert!(dot.contains("\"A\" -- \"b\" [label=\"2,3\""));
+
+        let path = ["start", "c"].map(|c| c.to_string()).to_vec();
+        assert!(caves_graph_to_dot(&graph, &policy, &DotOverlay::Path(path)).is_err());
+    }
+
+    #[test]
+    fn start_visited_once() {
+        let graph = example_graph();
+        let policy = VisitPolicy { small_max_visits: 2, ..VisitPolicy::default() };
+        assert_eq!(solve(&graph, &policy).unwrap(), 54);
+
+        let mut policy = policy;
+        policy.max_visits.insert("start".to_string(), 2);
+        assert_eq!(solve(&graph, &policy).unwrap(), 346);
+    }
+
+    fn args(args: &[&str]) -> Result<Args, PolicyError> {
+        parse_args(&args.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn command_line() {
+        let parsed = args(&[]).unwrap();
+        assert_eq!(parsed.filename, "input.txt");
+        assert!(parsed.policy.is_none());
+
+        let parsed = args(&["--small-max", "2", "--max-visits", "A=3", "--dot", "caves.dot"]).unwrap();
+        let policy = parsed.policy.unwrap();
+        assert_eq!(policy.small_max_visits, 2);
+        assert_eq!(policy.max_visits_of("A"), Some(3));
+        assert_eq!(parsed.dot_filename.as_deref(), Some("caves.dot"));
+
+        assert!(args(&["--small-max"]).is_err());
+        assert!(args(&["--colour", "red"]).unwrap_err().what.contains("Unknown option"));
+        assert!(args(&["--small-max", "two"]).unwrap_err().what.contains("Invalid number"));
+        assert!(args(&["--max-visits", "A"]).is_err());
+        assert!(args(&["--max-visits", "A=x"]).is_err());
+        assert!(args(&["--dot-overlay", "usage"]).unwrap_err().what.contains("requires --dot"));
+    }
+
+    #[test]
+    fn policy_lines() {
+        let lines = "# Visit b twice\n\nstart A\nmax-visits  b=2\n  require d\n";
+        let mut policy = VisitPolicy::default();
+        parse_policy_lines(lines.lines().map(|l| l.to_string()), &mut policy).unwrap();
+        assert_eq!(policy.start, "A");
+        assert_eq!(policy.max_visits_of("b"), Some(2));
+        assert_eq!(policy.required, vec!["d".to_string()]);
+
+        let mut policy = VisitPolicy::default();
+        assert!(parse_policy_lines(["small-max"].map(|l| l.to_string()).into_iter(), &mut policy).is_err());
+        assert!(parse_policy_lines(["big-max 2"].map(|l| l.to_string()).into_iter(), &mut policy).is_err());
+    }
+
+    #[test]
+    fn max_visits_hard_limit() {
+        let graph = example_graph();
+        let mut capped = VisitPolicy { extra_revisits: 1, ..VisitPolicy::default() };
+        capped.max_visits.insert("b".to_string(), 0);
+        let forbidden = VisitPolicy { extra_revisits: 1, forbidden: vec!["b".to_string()], ..VisitPolicy::default() };
+        assert_eq!(solve(&graph, &capped).unwrap(), solve(&graph, &forbidden).unwrap());
+
+        capped.max_visits.insert("b".to_string(), 1);
+        assert_eq!(solve(&graph, &capped).unwrap(), 16);
+    }
+}
@@ -1,66 +1,146 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 
 type Grid = BTreeMap<usize, BTreeSet<usize>>;
 
+const GLYPH_W: usize = 4;
+const GLYPH_H: usize = 6;
+const GLYPH_PITCH: usize = GLYPH_W + 1;
+
+/// Advent of Code's 4x6 capital letters font. Y is 5 columns wide, so it
+/// isn't supported and it's reported as an unrecognized glyph.
+const FONT: [(char, [&str; GLYPH_H]); 17] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Fold {
     X(usize),
     Y(usize),
 }
 
+#[derive(Clone, Debug, PartialEq)]
+struct Sheet {
+    points: Grid,
+    width: usize,
+    height: usize,
+}
+
 fn main() {
     let f = File::open("input.txt").expect("Can't open input.txt");
     let reader = BufReader::new(f);
     let mut lines = reader.lines().map(|l| l.unwrap());
 
-    let mut points = parse_grid(&mut lines);
-    let folds = parse_folds(&mut lines);
+    let sheet = Sheet::new(parse_grid(&mut lines));
+    let snapshots = parse_folds(&mut lines)
+        .and_then(|folds| fold_all(&sheet, &folds))
+        .unwrap_or_else(|e| panic!("{}", e));
 
-    let mut first_fold_done = false;
-    for fold in folds {
-        match fold {
-            Fold::X(num) => fold_x(&mut points, num),
-            Fold::Y(num) => fold_y(&mut points, num),
-        }
+    let first = snapshots.first().expect("No folds");
+    println!("Part 1: points count={}", count_points(&first.points));
 
-        if !first_fold_done {
-            first_fold_done = true;
-            println!("Part 1: points count={}", count_points(&points));
+    let last = snapshots.last().unwrap();
+    match read_code(&last.points) {
+        Ok(code) => println!("Part 2: code={}", code),
+        Err(e) => {
+            println!("Part 2: {}, code:", e);
+            print_points(&last.points);
         }
     }
+}
 
-    println!("Part 2: code:");
-    print_points(&points);
+/// Apply all the folds, returning the state of the sheet after each of them
+fn fold_all(sheet: &Sheet, folds: &[Fold]) -> Result<Vec<Sheet>, FoldError> {
+    let mut snapshots = Vec::with_capacity(folds.len());
+    let mut sheet = sheet.clone();
+    for fold in folds {
+        sheet.fold(*fold)?;
+        snapshots.push(sheet.clone());
+    }
+    Ok(snapshots)
 }
 
-fn fold_x(points: &mut Grid, num: usize) {
-    for (_, row) in points.iter_mut() {
-        let folded_cols: Vec<usize> = row.range(num + 1..).copied().collect();
-        for x in folded_cols {
-            row.insert(num - (x - num));
-            row.remove(&x);
+impl Sheet {
+    fn new(points: Grid) -> Self {
+        let height = points.keys().last().map_or(0, |y| y + 1);
+        let width = points.values().filter_map(|row| row.last()).max().map_or(0, |x| x + 1);
+        Sheet { points, width, height }
+    }
+
+    /// Fold the sheet, keeping the top/left half in place. If the bottom/right
+    /// half is larger, the coordinates are shifted so they start at 0 again.
+    fn fold(&mut self, fold: Fold) -> Result<(), FoldError> {
+        match fold {
+            Fold::X(num) => self.fold_x(num),
+            Fold::Y(num) => self.fold_y(num),
         }
-        row.remove(&num);
     }
-}
 
-fn fold_y(points: &mut Grid, num: usize) {
-    let folded_rows = points.split_off(&(num + 1));
-    for (y, mut row) in folded_rows {
-        points.entry(num - (y - num)).or_default().append(&mut row);
+    fn fold_x(&mut self, num: usize) -> Result<(), FoldError> {
+        let on_line: Vec<(usize, usize)> = self.points.iter()
+            .filter(|(_, row)| row.contains(&num))
+            .map(|(y, _)| (num, *y))
+            .collect();
+        if !on_line.is_empty() {
+            return Err(FoldError::on_line("x", num, &on_line));
+        }
+
+        let (left, right) = (num, self.width.saturating_sub(num + 1));
+        let shift = right.saturating_sub(left);
+        for row in self.points.values_mut() {
+            *row = row.iter()
+                .map(|&x| if x < num { x + shift } else { 2 * num + shift - x })
+                .collect();
+        }
+        self.width = left.max(right);
+        Ok(())
+    }
+
+    fn fold_y(&mut self, num: usize) -> Result<(), FoldError> {
+        if let Some(row) = self.points.get(&num) {
+            let on_line: Vec<(usize, usize)> = row.iter().map(|x| (*x, num)).collect();
+            return Err(FoldError::on_line("y", num, &on_line));
+        }
+
+        let (top, bottom) = (num, self.height.saturating_sub(num + 1));
+        let shift = bottom.saturating_sub(top);
+        let mut points = Grid::new();
+        for (y, mut row) in std::mem::take(&mut self.points) {
+            let y = if y < num { y + shift } else { 2 * num + shift - y };
+            points.entry(y).or_default().append(&mut row);
+        }
+        self.points = points;
+        self.height = top.max(bottom);
+        Ok(())
     }
-    points.remove(&num);
 }
 
 fn count_points(points: &Grid) -> u32 {
-    points.iter()
-        .map(|(_y, row)| row.len() as u32)
+    points.values()
+        .map(|row| row.len() as u32)
         .sum::<u32>()
 }
 
 fn print_points(points: &Grid) {
-    for (_, row) in points {
+    for row in points.values() {
         let last = *row.iter().last().unwrap();
         for x in 0..=last {
             if row.contains(&x) {
@@ -73,6 +153,47 @@ fn print_points(points: &Grid) {
     }
 }
 
+/// Recognize the letters formed by the points, written with the 4x6 font and
+/// separated by 1 empty column.
+fn read_code(points: &Grid) -> Result<String, OcrError> {
+    let min_y = *points.keys().next().ok_or_else(|| OcrError::new("No points".to_string()))?;
+    let max_y = *points.keys().last().unwrap();
+    // Glyphs are anchored at x=0, as some of them have the 1st column empty
+    let min_x = points.values().filter_map(|row| row.first()).copied().min().unwrap();
+    let min_x = min_x - min_x % GLYPH_PITCH;
+    let max_x = points.values().filter_map(|row| row.last()).copied().max().unwrap();
+
+    if max_y - min_y + 1 != GLYPH_H {
+        return Err(OcrError::new(format!("Expected {} rows of text, got {}", GLYPH_H, max_y - min_y + 1)));
+    }
+
+    let glyphs_count = (max_x - min_x) / GLYPH_PITCH + 1;
+    let mut code = String::with_capacity(glyphs_count);
+
+    for i in 0..glyphs_count {
+        let x0 = min_x + i * GLYPH_PITCH;
+        let block: Vec<String> = (min_y..=max_y)
+            .map(|y| {
+                (x0..x0 + GLYPH_PITCH)
+                    .map(|x| if points.get(&y).is_some_and(|row| row.contains(&x)) { '#' } else { '.' })
+                    .collect()
+            })
+            .collect();
+
+        let glyph = FONT.iter()
+            .find(|(_, rows)| rows.iter().zip(&block).all(|(r, b)| b.starts_with(r) && b.ends_with('.')));
+
+        match glyph {
+            Some((ch, _)) => code.push(*ch),
+            None => return Err(OcrError::new(
+                format!("Unrecognized glyph #{} at x={}:\n{}", i, x0, block.join("\n"))
+            )),
+        }
+    }
+
+    Ok(code)
+}
+
 fn parse_grid<T>(lines: &mut T) -> Grid
 where
     T: Iterator<Item = String>
@@ -91,24 +212,189 @@ fn parse_points(line: &str) -> (usize, usize) {
     (split.next().unwrap(), split.next().unwrap())
 }
 
-fn parse_folds<T>(lines: &mut T) -> Vec<Fold>
+fn parse_folds<T>(lines: &mut T) -> Result<Vec<Fold>, FoldError>
 where
     T: Iterator<Item = String>
 {
     lines.map(|s| parse_fold(&s)).collect()
 }
 
-fn parse_fold(line: &str) -> Fold {
-    let mut split = line.split_ascii_whitespace().skip(2)
-        .next().unwrap()
-        .split('=');
-    
-    let axis = split.next().unwrap();
-    let num = split.next().unwrap().parse().unwrap();
+fn parse_fold(line: &str) -> Result<Fold, FoldError> {
+    let invalid = || FoldError::new(format!("Invalid fold '{}'", line));
+
+    let (axis, num) = line.strip_prefix("fold along ")
+        .and_then(|s| s.split_once('='))
+        .ok_or_else(invalid)?;
+    let num = num.parse().map_err(|_| invalid())?;
 
     match axis {
-        "x" => Fold::X(num),
-        "y" => Fold::Y(num),
-        _ => panic!(),
+        "x" => Ok(Fold::X(num)),
+        "y" => Ok(Fold::Y(num)),
+        _ => Err(FoldError::new(format!("Invalid fold axis '{}'", axis))),
+    }
+}
+
+#[derive(Clone, Debug)]
+struct OcrError {
+    what: String,
+}
+
+impl OcrError {
+    fn new(what: String) -> Self {
+        OcrError { what }
+    }
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.what)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct FoldError {
+    what: String,
+}
+
+impl FoldError {
+    fn new(what: String) -> Self {
+        FoldError { what }
+    }
+
+    fn on_line(axis: &str, num: usize, points: &[(usize, usize)]) -> Self {
+        let points: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+        FoldError::new(format!("Dots on fold line {}={}: {}", axis, num, points.join(" ")))
+    }
+}
+
+impl fmt::Display for FoldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.what)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_from_art(art: &[&str]) -> Grid {
+        let mut points = Grid::new();
+        for (y, line) in art.iter().enumerate() {
+            for (x, ch) in line.chars().enumerate() {
+                if ch == '#' {
+                    points.entry(y).or_default().insert(x);
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn ocr() {
+        let points = grid_from_art(&[
+            "#  #   ## ###  #  # #### #  # ###   ## ",
+            "# #     # #  # # #  #    #  # #  # #  #",
+            "##      # ###  ##   ###  #  # ###  #   ",
+            "# #     # #  # # #  #    #  # #  # # ##",
+            "# #  #  # #  # # #  #    #  # #  # #  #",
+            "#  #  ##  ###  #  # ####  ##  ###   ###",
+        ]);
+        assert_eq!(read_code(&points).unwrap(), "KJBKEUBG");
+    }
+
+    #[test]
+    fn ocr_empty_first_column() {
+        let points = grid_from_art(&[
+            " ### #  #",
+            "  #  #  #",
+            "  #  ####",
+            "  #  #  #",
+            "  #  #  #",
+            " ### #  #",
+        ]);
+        assert_eq!(read_code(&points).unwrap(), "IH");
+    }
+
+    #[test]
+    fn ocr_unrecognized() {
+        let points = grid_from_art(&[
+            "#### ####",
+            "#  # #  #",
+            "#  # #  #",
+            "#  # #  #",
+            "#  # #  #",
+            "#### ####",
+        ]);
+        let err = read_code(&points).unwrap_err();
+        assert!(err.what.starts_with("Unrecognized glyph #0"));
+        assert!(err.what.ends_with("####."));
+
+        let points = grid_from_art(&[
+            "#...#",
+            "#...#",
+            ".#.#.",
+            "..#..",
+            "..#..",
+            "..#..",
+        ]);
+        let err = read_code(&points).unwrap_err();
+        assert!(err.what.starts_with("Unrecognized glyph #0 at x=0"), "{}", err.what);
+    }
+
+    #[test]
+    fn folds() {
+        let points = grid_from_art(&[
+            "...#..#..#.",
+            "....#......",
+            "...........",
+            "#..........",
+            "...#....#.#",
+            "...........",
+            "...........",
+            "...........",
+            "...........",
+            "...........",
+            ".#....#.##.",
+            "....#......",
+            "......#...#",
+            "#..........",
+            "#.#........",
+        ]);
+        let sheet = Sheet::new(points);
+        let snapshots = fold_all(&sheet, &[Fold::Y(7), Fold::X(5)]).unwrap();
+        assert_eq!(count_points(&snapshots[0].points), 17);
+        assert_eq!(count_points(&snapshots[1].points), 16);
+        assert_eq!((snapshots[1].width, snapshots[1].height), (5, 7));
+    }
+
+    #[test]
+    fn fold_asymmetric() {
+        let points = grid_from_art(&[
+            "#.....#",
+        ]);
+        let mut sheet = Sheet::new(points);
+        sheet.fold(Fold::X(2)).unwrap();
+        assert_eq!(sheet.width, 4);
+        assert_eq!(sheet.points[&0], BTreeSet::from([0, 2]));
+    }
+
+    #[test]
+    fn fold_errors() {
+        let points = grid_from_art(&[
+            "#.#",
+            "...",
+            "..#",
+        ]);
+        let mut sheet = Sheet::new(points);
+        assert_eq!(sheet.fold(Fold::X(2)).unwrap_err().what, "Dots on fold line x=2: 2,0 2,2");
+        // The sheet size comes from the dots, so the far half can be empty
+        sheet.fold(Fold::Y(5)).unwrap();
+        assert_eq!(sheet.points, grid_from_art(&["#.#", "...", "..#"]));
+        assert_eq!(sheet.height, 5);
+        sheet.fold(Fold::X(3)).unwrap();
+        assert_eq!(sheet.points, grid_from_art(&["#.#", "...", "..#"]));
+        assert_eq!(sheet.width, 3);
+        assert!(parse_fold("fold along z=3").is_err());
+        assert_eq!(parse_fold("fold along y=3").unwrap(), Fold::Y(3));
     }
 }
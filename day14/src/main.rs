@@ -1,51 +1,481 @@
+use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
+use std::fmt;
 
 type Insertions = BTreeMap<(char, char), char>;
 type PairsCounters = BTreeMap<(char, char), u64>;
 type CharsCounters = BTreeMap<char, u64>;
+type Matrix<T> = Vec<Vec<T>>;
+
+const MAX_EXPANDED_LEN: usize = 1 << 28;
+const MAX_EXACT_STEPS: u64 = 10_000;
+const MAX_GROWTH_STEPS: u64 = 1_000;
+
+#[derive(Default)]
+struct Args {
+    steps: Option<u64>,
+    modulo: Option<u64>,
+    expand: Option<u64>,
+    growth_csv: Option<String>,
+}
 
 fn main() {
     let (polymer, insertions) = parse_input("input.txt");
-    let (mut pairs_counts, mut chars_counts) = create_counters(&polymer);
 
-    for i in 1..=40 {
-        let mut new_pairs_counts = PairsCounters::new();
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        let args = parse_args(&args).unwrap_or_else(|e| panic!("{}", e));
+        run_with_args(&args, &polymer, &insertions);
+        return;
+    }
+
+    let chars_counts = count_elements(&BigRing, &polymer, &insertions, 10);
+    let (min, max) = min_max(&chars_counts);
+    println!("Part 1: max={}, min={}, max-min={}", max, min, max - min);
 
-        for ((ch0, ch1), count) in pairs_counts {
-            let ch_insert = *insertions.get(&(ch0, ch1)).unwrap();
+    let chars_counts = count_elements(&BigRing, &polymer, &insertions, 40);
+    let (min, max) = min_max(&chars_counts);
+    println!("Part 2: max={}, min={}, max-min={}", max, min, max - min);
+}
+
+fn run_with_args(args: &Args, polymer: &str, insertions: &Insertions) {
+    if let Some(steps) = args.expand {
+        let expanded = expand_polymer(polymer, insertions, steps).unwrap_or_else(|e| panic!("{}", e));
+        println!("{}", expanded);
 
-            chars_counts
-                .entry(ch_insert)
-                .and_modify(|v| *v += count)
-                .or_insert(count);
-            new_pairs_counts
-                .entry((ch0, ch_insert))
-                .and_modify(|v| *v += count)
-                .or_insert(count);
-            new_pairs_counts
-                .entry((ch_insert, ch1))
-                .and_modify(|v| *v += count)
-                .or_insert(count);
+        let mut expanded_counts = BTreeMap::new();
+        for ch in expanded.chars() {
+            *expanded_counts.entry(ch).or_insert(0u64) += 1;
         }
+        let expanded_counts: BTreeMap<char, BigUint> = expanded_counts.into_iter()
+            .map(|(ch, count)| (ch, BigUint::from(count)))
+            .collect();
+        let mut counts = count_elements(&BigRing, polymer, insertions, steps);
+        counts.retain(|_, count| *count != BigUint::from(0));
+        assert_eq!(counts, expanded_counts, "Counts don't match the expanded polymer");
+        println!("Counts match the expanded polymer (length={})", expanded.len());
+    }
 
-        pairs_counts = new_pairs_counts;
+    let Some(steps) = args.steps else {
+        return;
+    };
 
-        if i == 10 {
-            let min = chars_counts.values().min().unwrap();
-            let max = chars_counts.values().max().unwrap();
-            println!("Part 1: max={}, min={}, max-min={}", max, min, max - min);
+    if let Some(filename) = &args.growth_csv {
+        let result = match args.modulo {
+            Some(m) => write_growth_csv(filename, growth_curves(&ModRing(m), polymer, insertions, steps)),
+            None => write_growth_csv(filename, growth_curves(&BigRing, polymer, insertions, steps)),
+        };
+        result.unwrap_or_else(|e| panic!("Can't write {}: {}", filename, e));
+        println!("Growth curves written to {}", filename);
+    } else {
+        match args.modulo {
+            Some(m) => print_counts(&count_elements(&ModRing(m), polymer, insertions, steps)),
+            None => print_counts(&count_elements(&BigRing, polymer, insertions, steps)),
         }
     }
+}
 
-    let min = chars_counts.values().min().unwrap();
+/// Options:
+///   --steps N          print the elements counts after N steps
+///   --modulo M         counts modulo M, otherwise exact (up to 10000 steps)
+///   --growth-csv FILE  write the counts at every step up to N to a CSV file
+///                      (up to 1000 steps)
+///   --expand N         print the polymer after N steps, checking the counts
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut parsed = Args::default();
+
+    let mut args = args.iter();
+    while let Some(opt) = args.next() {
+        let value = args.next().ok_or(format!("Missing value for option '{}'", opt))?;
+        let num = || value.parse::<u64>().map_err(|_| format!("Invalid number '{}'", value));
+        match opt.as_str() {
+            "--steps" => parsed.steps = Some(num()?),
+            "--expand" => parsed.expand = Some(num()?),
+            "--growth-csv" => parsed.growth_csv = Some(value.clone()),
+            "--modulo" => match num()? {
+                0 => return Err("Modulo must be greater than 0".to_string()),
+                m => parsed.modulo = Some(m),
+            },
+            _ => return Err(format!("Unknown option '{}'", opt)),
+        }
+    }
+
+    if parsed.growth_csv.is_some() && parsed.steps.is_none() {
+        return Err("Option --growth-csv requires --steps".to_string());
+    }
+    if let (Some(steps), Some(_)) = (parsed.steps, &parsed.growth_csv) {
+        if steps > MAX_GROWTH_STEPS {
+            return Err(format!("Too many steps for --growth-csv, the max is {}", MAX_GROWTH_STEPS));
+        }
+    }
+    if let (Some(steps), None) = (parsed.steps, parsed.modulo) {
+        if steps > MAX_EXACT_STEPS {
+            return Err(format!("Exact counts after {} steps are too big, use --modulo", steps));
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn print_counts<T: fmt::Display>(chars_counts: &BTreeMap<char, T>) {
+    for (ch, count) in chars_counts {
+        println!("{}: {}", ch, count);
+    }
+}
+
+fn min_max(chars_counts: &BTreeMap<char, BigUint>) -> (&BigUint, &BigUint) {
+    // Elements that can appear in later steps are counted as 0 if not present
+    let zero = BigUint::from(0);
+    let min = chars_counts.values().filter(|count| **count != zero).min().unwrap();
     let max = chars_counts.values().max().unwrap();
-    println!("Part 2: max={}, min={}, max-min={}", max, min, max - min);
+    (min, max)
+}
+
+/// Count the elements of the polymer after the given number of steps. Pairs
+/// counts evolve as a linear transformation, so applying it `steps` times is
+/// done raising the transition matrix to the power of `steps` by squaring.
+/// Pairs without insertion rule are kept unchanged.
+fn count_elements<R: Ring>(ring: &R, polymer: &str, insertions: &Insertions, steps: u64) -> BTreeMap<char, R::Num> {
+    let (pairs, mut transitions) = create_transitions(ring, polymer, insertions);
+    let mut counts = initial_pairs_counts(ring, polymer, &pairs);
+
+    let mut steps = steps;
+    while steps > 0 {
+        if steps & 1 == 1 {
+            counts = vec_mul(ring, &counts, &transitions);
+        }
+        steps >>= 1;
+        if steps > 0 {
+            transitions = mat_mul(ring, &transitions, &transitions);
+        }
+    }
+
+    pairs_to_chars_counts(ring, polymer, &pairs, &counts)
+}
+
+/// Elements counts at every step, from the template (step 0) to `steps`,
+/// calculated lazily one step at a time
+fn growth_curves<'a, R: Ring>(ring: &'a R, polymer: &'a str, insertions: &Insertions, steps: u64)
+    -> impl Iterator<Item = BTreeMap<char, R::Num>> + 'a
+{
+    let (pairs, transitions) = create_transitions(ring, polymer, insertions);
+    let mut counts = initial_pairs_counts(ring, polymer, &pairs);
+
+    (0..=steps).map(move |step| {
+        if step > 0 {
+            counts = vec_mul(ring, &counts, &transitions);
+        }
+        pairs_to_chars_counts(ring, polymer, &pairs, &counts)
+    })
+}
+
+/// Pairs that can appear in the polymer and the matrix with how many of each
+/// pair (columns) are generated by each pair (rows) in one step
+fn create_transitions<R: Ring>(ring: &R, polymer: &str, insertions: &Insertions) -> (Vec<(char, char)>, Matrix<R::Num>) {
+    let (pairs_counts, _) = create_counters(polymer);
+    let pairs = reachable_pairs(&pairs_counts, insertions);
+    let index: BTreeMap<(char, char), usize> = pairs.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+    let mut transitions = vec![vec![ring.zero(); pairs.len()]; pairs.len()];
+    for (i, &(ch0, ch1)) in pairs.iter().enumerate() {
+        let next_pairs = match insertions.get(&(ch0, ch1)) {
+            Some(&ch_insert) => vec![(ch0, ch_insert), (ch_insert, ch1)],
+            None => vec![(ch0, ch1)],
+        };
+        for pair in next_pairs {
+            let j = index[&pair];
+            transitions[i][j] = ring.add(&transitions[i][j], &ring.one());
+        }
+    }
+
+    (pairs, transitions)
+}
+
+fn initial_pairs_counts<R: Ring>(ring: &R, polymer: &str, pairs: &[(char, char)]) -> Vec<R::Num> {
+    let (pairs_counts, _) = create_counters(polymer);
+    pairs.iter()
+        .map(|pair| ring.num(pairs_counts.get(pair).copied().unwrap_or(0)))
+        .collect()
+}
+
+fn pairs_to_chars_counts<R: Ring>(ring: &R, polymer: &str, pairs: &[(char, char)], counts: &[R::Num]) -> BTreeMap<char, R::Num> {
+    // Count only the 1st element of each pair, as pairs overlap. The last
+    // element of the polymer never changes.
+    let mut chars_counts = BTreeMap::new();
+    for (&(ch0, _), count) in pairs.iter().zip(counts) {
+        let entry = chars_counts.entry(ch0).or_insert_with(|| ring.zero());
+        *entry = ring.add(entry, count);
+    }
+    if let Some(last) = polymer.chars().last() {
+        let entry = chars_counts.entry(last).or_insert_with(|| ring.zero());
+        *entry = ring.add(entry, &ring.one());
+    }
+
+    chars_counts
+}
+
+/// Build the actual polymer, only feasible for a small number of steps as its
+/// length nearly doubles in each one
+fn expand_polymer(polymer: &str, insertions: &Insertions, steps: u64) -> Result<String, String> {
+    // Each step at most doubles the number of pairs
+    let pairs = polymer.chars().count().saturating_sub(1);
+    if pairs == 0 {
+        return Ok(polymer.to_string());
+    }
+    let max_pairs = u32::try_from(steps).ok()
+        .and_then(|steps| 1usize.checked_shl(steps))
+        .and_then(|factor| factor.checked_mul(pairs))
+        .filter(|max_pairs| *max_pairs < MAX_EXPANDED_LEN);
+    if max_pairs.is_none() {
+        return Err(format!("Polymer too long to expand {} steps", steps));
+    }
+
+    let mut polymer: Vec<char> = polymer.chars().collect();
+    for _ in 0..steps {
+        let mut new_polymer = Vec::with_capacity(2 * polymer.len());
+        for chs in polymer.windows(2) {
+            new_polymer.push(chs[0]);
+            if let Some(&ch_insert) = insertions.get(&(chs[0], chs[1])) {
+                new_polymer.push(ch_insert);
+            }
+        }
+        new_polymer.extend(polymer.last());
+        polymer = new_polymer;
+    }
+
+    Ok(polymer.into_iter().collect())
+}
+
+/// Write the curves as they are calculated. All the steps have the same
+/// elements, so the 1st one gives the header.
+fn write_growth_csv<T, I>(filename: &str, curves: I) -> io::Result<()>
+where
+    T: fmt::Display,
+    I: Iterator<Item = BTreeMap<char, T>>
+{
+    let mut curves = curves.peekable();
+    let chars: Vec<char> = curves.peek().map(|c| c.keys().copied().collect()).unwrap_or_default();
+
+    let mut f = BufWriter::new(File::create(filename)?);
+    write!(f, "step")?;
+    for ch in &chars {
+        write!(f, ",{}", ch)?;
+    }
+    writeln!(f)?;
+
+    for (step, counts) in curves.enumerate() {
+        write!(f, "{}", step)?;
+        for ch in &chars {
+            match counts.get(ch) {
+                Some(count) => write!(f, ",{}", count)?,
+                None => write!(f, ",0")?,
+            }
+        }
+        writeln!(f)?;
+    }
+
+    f.flush()
+}
+
+/// All the pairs that can appear in the polymer, so the matrix doesn't need a
+/// row for every possible pair
+fn reachable_pairs(pairs_counts: &PairsCounters, insertions: &Insertions) -> Vec<(char, char)> {
+    let mut pairs: Vec<(char, char)> = pairs_counts.keys().copied().collect();
+    let mut i = 0;
+    while i < pairs.len() {
+        let (ch0, ch1) = pairs[i];
+        if let Some(&ch_insert) = insertions.get(&(ch0, ch1)) {
+            for pair in [(ch0, ch_insert), (ch_insert, ch1)] {
+                if !pairs.contains(&pair) {
+                    pairs.push(pair);
+                }
+            }
+        }
+        i += 1;
+    }
+    pairs
+}
+
+fn vec_mul<R: Ring>(ring: &R, v: &[R::Num], m: &Matrix<R::Num>) -> Vec<R::Num> {
+    let mut result = vec![ring.zero(); v.len()];
+    for (i, vi) in v.iter().enumerate() {
+        for (j, r) in result.iter_mut().enumerate() {
+            *r = ring.add(r, &ring.mul(vi, &m[i][j]));
+        }
+    }
+    result
+}
+
+fn mat_mul<R: Ring>(ring: &R, a: &Matrix<R::Num>, b: &Matrix<R::Num>) -> Matrix<R::Num> {
+    a.iter().map(|row| vec_mul(ring, row, b)).collect()
+}
+
+/// Arithmetic used to count: `ModRing` for counts modulo a value, that can be
+/// calculated for any number of steps, `BigRing` for exact counts.
+trait Ring {
+    type Num: Clone + fmt::Display;
+    fn zero(&self) -> Self::Num;
+    fn one(&self) -> Self::Num;
+    fn num(&self, v: u64) -> Self::Num;
+    fn add(&self, a: &Self::Num, b: &Self::Num) -> Self::Num;
+    fn mul(&self, a: &Self::Num, b: &Self::Num) -> Self::Num;
+}
+
+struct ModRing(u64);
+struct BigRing;
+
+impl Ring for ModRing {
+    type Num = u64;
+
+    fn zero(&self) -> u64 { 0 }
+    fn one(&self) -> u64 { 1 % self.0 }
+    fn num(&self, v: u64) -> u64 { v % self.0 }
+
+    fn add(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 + *b as u128) % self.0 as u128) as u64
+    }
+
+    fn mul(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 * *b as u128) % self.0 as u128) as u64
+    }
+}
+
+impl Ring for BigRing {
+    type Num = BigUint;
+
+    fn zero(&self) -> BigUint { BigUint::from(0) }
+    fn one(&self) -> BigUint { BigUint::from(1) }
+    fn num(&self, v: u64) -> BigUint { BigUint::from(v) }
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint { a + b }
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint { a * b }
+}
+
+/// Unsigned integer of arbitrary size, with base 2^32 little endian digits and
+/// no leading zeros
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct BigUint {
+    digits: Vec<u32>,
+}
+
+impl BigUint {
+    fn normalized(mut digits: Vec<u32>) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        BigUint { digits }
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(v: u64) -> Self {
+        BigUint::normalized(vec![v as u32, (v >> 32) as u32])
+    }
+}
+
+impl std::ops::Add for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let len = self.digits.len().max(other.digits.len());
+        let mut digits = Vec::with_capacity(len + 1);
+        let mut carry = 0u64;
+        for i in 0..len {
+            let a = *self.digits.get(i).unwrap_or(&0) as u64;
+            let b = *other.digits.get(i).unwrap_or(&0) as u64;
+            let sum = a + b + carry;
+            digits.push(sum as u32);
+            carry = sum >> 32;
+        }
+        digits.push(carry as u32);
+        BigUint::normalized(digits)
+    }
+}
+
+impl std::ops::Sub for &BigUint {
+    type Output = BigUint;
+
+    fn sub(self, other: &BigUint) -> BigUint {
+        assert!(*self >= *other, "BigUint subtraction overflow");
+        let mut digits = Vec::with_capacity(self.digits.len());
+        let mut borrow = 0i64;
+        for (i, a) in self.digits.iter().enumerate() {
+            let b = *other.digits.get(i).unwrap_or(&0) as i64;
+            let mut diff = *a as i64 - b - borrow;
+            borrow = if diff < 0 { 1 } else { 0 };
+            if diff < 0 {
+                diff += 1 << 32;
+            }
+            digits.push(diff as u32);
+        }
+        BigUint::normalized(digits)
+    }
+}
+
+impl std::ops::Mul for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        let mut digits = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, a) in self.digits.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.digits.iter().enumerate() {
+                let v = digits[i + j] as u64 + *a as u64 * *b as u64 + carry;
+                digits[i + j] = v as u32;
+                carry = v >> 32;
+            }
+            digits[i + other.digits.len()] = carry as u32;
+        }
+        BigUint::normalized(digits)
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.digits.len().cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Divide repeatedly by 10^9 to get the decimal digits in groups of 9
+        let mut groups = Vec::new();
+        let mut digits = self.digits.clone();
+        while !digits.is_empty() {
+            let mut rem = 0u64;
+            for d in digits.iter_mut().rev() {
+                let v = (rem << 32) | *d as u64;
+                *d = (v / 1_000_000_000) as u32;
+                rem = v % 1_000_000_000;
+            }
+            groups.push(rem as u32);
+            digits = BigUint::normalized(digits).digits;
+        }
+
+        match groups.pop() {
+            None => write!(f, "0"),
+            Some(first) => {
+                write!(f, "{}", first)?;
+                for group in groups.iter().rev() {
+                    write!(f, "{:09}", group)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 fn parse_input(filename: &str) -> (String, Insertions) {
-    let f = File::open(filename).expect(&format!("Can't open {}", filename));
+    let f = File::open(filename).unwrap_or_else(|_| panic!("Can't open {}", filename));
     let reader = BufReader::new(f);
     let mut lines = reader.lines().map(|l| l.unwrap());
 
@@ -56,9 +486,9 @@ fn parse_input(filename: &str) -> (String, Insertions) {
     let mut insertions = Insertions::new();
     for line in lines {
         let split: Vec<&str> = line.split(" -> ").collect();
-        let c1 = split[0].chars().nth(0).unwrap();
+        let c1 = split[0].chars().next().unwrap();
         let c2 = split[0].chars().nth(1).unwrap();
-        let cm = split[1].chars().nth(0).unwrap();
+        let cm = split[1].chars().next().unwrap();
         insertions.insert((c1, c2), cm);
     }
 
@@ -79,3 +509,102 @@ fn create_counters(polymer: &str) -> (PairsCounters, CharsCounters) {
 
     (pairs_counts, chars_counts)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_insertions() -> Insertions {
+        let rules = [
+            "CH B", "HH N", "CB H", "NH C", "HB C", "HC B", "HN C", "NN C",
+            "BH H", "NC B", "NB B", "BN B", "BB N", "BC B", "CC N", "CN C",
+        ];
+        rules.iter()
+            .map(|r| {
+                let r: Vec<char> = r.chars().collect();
+                ((r[0], r[1]), r[3])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn example_counts() {
+        let counts = count_elements(&BigRing, "NNCB", &example_insertions(), 10);
+        assert_eq!(counts[&'B'], BigUint::from(1749));
+        assert_eq!(counts[&'C'], BigUint::from(298));
+        assert_eq!(counts[&'H'], BigUint::from(161));
+        assert_eq!(counts[&'N'], BigUint::from(865));
+
+        let counts = count_elements(&BigRing, "NNCB", &example_insertions(), 40);
+        assert_eq!(counts[&'B'], BigUint::from(2192039569602));
+        assert_eq!(counts[&'H'], BigUint::from(3849876073));
+    }
+
+    #[test]
+    fn modulo_counts() {
+        // After 60 steps counts still fit in u64
+        let big_counts = count_elements(&BigRing, "NNCB", &example_insertions(), 60);
+        let mod_counts = count_elements(&ModRing(1_000_000_007), "NNCB", &example_insertions(), 60);
+        for (ch, count) in big_counts {
+            assert!(count.digits.len() <= 2);
+            let count = count.digits.iter().rev().fold(0u64, |acc, d| (acc << 32) | *d as u64);
+            assert_eq!(count % 1_000_000_007, mod_counts[&ch]);
+        }
+    }
+
+    #[test]
+    fn pairs_without_rule() {
+        let insertions = Insertions::from([(('A', 'B'), 'A')]);
+        let counts = count_elements(&BigRing, "ABBA", &insertions, 3);
+        // Only AB changes: ABBA -> AABBA -> AAABBA -> AAAABBA
+        assert_eq!(counts[&'A'], BigUint::from(5));
+        assert_eq!(counts[&'B'], BigUint::from(2));
+    }
+
+    #[test]
+    fn expanded_polymer() {
+        let insertions = example_insertions();
+        assert_eq!(expand_polymer("NNCB", &insertions, 2).unwrap(), "NBCCNBBBCBHCB");
+        assert!(expand_polymer("NNCB", &insertions, 40).is_err());
+        // 16 << 60 wraps to 0 in a plain shift
+        assert!(expand_polymer("NNCBNNCBNNCBNNCBN", &insertions, 60).is_err());
+        assert!(expand_polymer("NNCB", &insertions, u64::MAX).is_err());
+        assert_eq!(expand_polymer("N", &insertions, u64::MAX).unwrap(), "N");
+
+        for steps in 0..12 {
+            let expanded = expand_polymer("NNCB", &insertions, steps).unwrap();
+            let counts = count_elements(&BigRing, "NNCB", &insertions, steps);
+            for (ch, count) in counts {
+                let expanded_count = expanded.chars().filter(|c| *c == ch).count();
+                assert_eq!(count, BigUint::from(expanded_count as u64));
+            }
+        }
+    }
+
+    #[test]
+    fn growth() {
+        let curves: Vec<_> = growth_curves(&BigRing, "NNCB", &example_insertions(), 10).collect();
+        assert_eq!(curves.len(), 11);
+        assert_eq!(curves[0][&'N'], BigUint::from(2));
+        assert_eq!(curves[10], count_elements(&BigRing, "NNCB", &example_insertions(), 10));
+    }
+
+    #[test]
+    fn biguint_display() {
+        let a = BigUint::from(u64::MAX);
+        assert_eq!((&a * &a).to_string(), "340282366920938463426481119284349108225");
+        assert_eq!(BigUint::from(0).to_string(), "0");
+        assert_eq!((&(&a + &a) - &a), a);
+    }
+
+    #[test]
+    fn exact_steps_limit() {
+        let args = |a: &[&str]| parse_args(&a.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+        assert!(args(&["--steps", "10000"]).is_ok());
+        assert!(args(&["--steps", "1000000000000000000"]).err().unwrap().contains("--modulo"));
+        assert!(args(&["--steps", "1000000000000000000", "--modulo", "1000"]).is_ok());
+        assert!(args(&["--steps", "1000", "--growth-csv", "f.csv"]).is_ok());
+        let growth_args = ["--steps", "1000000000000000000", "--modulo", "7", "--growth-csv", "f.csv"];
+        assert!(args(&growth_args).err().unwrap().contains("--growth-csv"));
+    }
+}
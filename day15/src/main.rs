@@ -1,137 +1,264 @@
+use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::collections::{BinaryHeap, BTreeMap};
-use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::cmp::Reverse;
+
+type Pos = (usize, usize);
+
+const ORTHOGONAL_MOVES: [(isize, isize); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
+const DIAGONAL_MOVES: [(isize, isize); 8] = [
+    (-1, 0), (0, -1), (1, 0), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1),
+];
+const KNIGHT_MOVES: [(isize, isize); 8] = [
+    (-2, -1), (-1, -2), (1, -2), (2, -1), (2, 1), (1, 2), (-1, 2), (-2, 1),
+];
+
+/// Risk map made of the base map repeated `tiles` times in each axis, with
+/// risks increased by 1 for each tile to the right or down, wrapping from 9
+/// to 1. Risks of the tiles are calculated when needed from the base map.
+#[derive(Clone, Debug)]
+struct RiskMap {
+    tile_width: usize,
+    tile_height: usize,
+    risks: Vec<u8>,
+    tiles: (usize, usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Moves {
+    Orthogonal,
+    Diagonal,
+    Knight,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Route {
+    cost: u32,
+    path: Vec<Pos>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Highlight {
+    Ansi,
+    Dots,
+}
+
+struct Args {
+    tiles: (usize, usize),
+    moves: Moves,
+    highlight: Option<Highlight>,
+}
 
 fn main() {
-    let grid = parse_input("input.txt");
+    let map = RiskMap::new(parse_input("input.txt"));
 
-    let cost = solve(&grid);
-    println!("Part 1: cost={}", cost);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        let args = parse_args(&args).unwrap_or_else(|e| panic!("{}", e));
+        let map = map.tiled(args.tiles.0, args.tiles.1);
+        let route = solve(&map, args.moves).expect("No route found");
+        if let Some(highlight) = args.highlight {
+            print!("{}", render_route(&map, &route.path, highlight));
+        }
+        println!("cost={}", route.cost);
+        return;
+    }
 
-    let grid_x5 = get_full_grid(&grid);
-    let cost = solve(&grid_x5);
-    println!("Part 2: cost={}", cost);
+    let route = solve(&map, Moves::Orthogonal).expect("No route found");
+    println!("Part 1: cost={}", route.cost);
+
+    let route = solve(&map.tiled(5, 5), Moves::Orthogonal).expect("No route found");
+    println!("Part 2: cost={}", route.cost);
 }
 
-fn solve(grid: &Vec<Vec<u8>>) -> u32 {
-    let destination = (grid[0].len() - 1, grid.len() - 1);
+/// Options:
+///   --tiles XxY                  repeat the map X times horizontally and Y vertically
+///   --moves orthogonal|diagonal|knight
+///   --render ansi|dots           print the map with the route highlighted
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut tiles = (1, 1);
+    let mut moves = Moves::Orthogonal;
+    let mut highlight = None;
 
-    let mut visited: BTreeMap<(usize, usize), u32> = BTreeMap::new();
+    let mut args = args.iter();
+    while let Some(opt) = args.next() {
+        let value = args.next().ok_or(format!("Missing value for option '{}'", opt))?;
+        match (opt.as_str(), value.as_str()) {
+            ("--tiles", value) => {
+                let invalid = || format!("Invalid tiles '{}', expected XxY", value);
+                let (x, y) = value.split_once('x').ok_or_else(invalid)?;
+                tiles = (x.parse().map_err(|_| invalid())?, y.parse().map_err(|_| invalid())?);
+                if tiles.0 == 0 || tiles.1 == 0 {
+                    return Err(invalid());
+                }
+            },
+            ("--moves", "orthogonal") => moves = Moves::Orthogonal,
+            ("--moves", "diagonal") => moves = Moves::Diagonal,
+            ("--moves", "knight") => moves = Moves::Knight,
+            ("--render", "ansi") => highlight = Some(Highlight::Ansi),
+            ("--render", "dots") => highlight = Some(Highlight::Dots),
+            _ => return Err(format!("Invalid option '{} {}'", opt, value)),
+        }
+    }
+
+    Ok(Args { tiles, moves, highlight })
+}
+
+/// A* search of the lowest risk route from top-left to bottom-right corners.
+/// The heuristic is the minimum number of moves to the destination times the
+/// lowest risk of the map, which never overestimates, so the first time that a
+/// position is taken from the queue its cost is already the lowest possible.
+fn solve(map: &RiskMap, moves: Moves) -> Option<Route> {
+    if map.risks.is_empty() {
+        return None;
+    }
+
+    let start = 0;
+    let dst = map.len() - 1;
+    let min_risk = map.min_risk() as u32;
+    let heuristic = |idx: usize| {
+        let (x, y) = map.pos(idx);
+        let (dx, dy) = (map.width() - 1 - x, map.height() - 1 - y);
+        let min_moves = match moves {
+            Moves::Orthogonal => dx + dy,
+            Moves::Diagonal => dx.max(dy),
+            Moves::Knight => (dx + dy).div_ceil(3).max(dx.max(dy).div_ceil(2)),
+        };
+        min_risk * min_moves as u32
+    };
+
+    let mut costs = vec![u32::MAX; map.len()];
+    let mut prev = vec![usize::MAX; map.len()];
+    let mut done = vec![false; map.len()];
     let mut pri_queue = BinaryHeap::new();
-    pri_queue.push(Node { cost: 0, pos: (0, 0), dst: destination });
-    let mut min_cost = None;
 
-    while let Some(node) = pri_queue.pop() {
-        if node.pos == destination {
-            if node.cost < min_cost.unwrap_or(std::u32::MAX) {
-                min_cost = Some(node.cost);
-            }
+    costs[start] = 0;
+    pri_queue.push(Reverse((heuristic(start), start)));
+
+    while let Some(Reverse((_, idx))) = pri_queue.pop() {
+        if done[idx] {
             continue;
         }
+        done[idx] = true;
 
-        if node.best_final_cost() >= min_cost.unwrap_or(std::u32::MAX) {
-            continue;
+        if idx == dst {
+            break;
         }
-        
-        for pos in node.adjacent_positions() {
-            let new_cost = node.cost + grid[pos.1 as usize][pos.0 as usize] as u32;
-            
-            if !visited.contains_key(&pos) || new_cost < visited[&pos] {
-                visited.insert(pos, new_cost);
-                pri_queue.push(Node {
-                    cost: new_cost,
-                    pos: pos,
-                    dst: destination 
-                });
+
+        for next in map.adjacent(idx, moves) {
+            let new_cost = costs[idx] + map.risk(next) as u32;
+            if new_cost < costs[next] {
+                costs[next] = new_cost;
+                prev[next] = idx;
+                pri_queue.push(Reverse((new_cost + heuristic(next), next)));
             }
         }
     }
 
-    min_cost.unwrap()
-}
-
-fn get_full_grid(grid: &Vec<Vec<u8>>) -> Vec<Vec<u8>> {
-    let tile_len = grid.len();
-    let full_len = 5 * tile_len;
+    if !done[dst] {
+        return None;
+    }
 
-    let mut grid_x5: Vec<Vec<u8>> = Vec::with_capacity(full_len);
-    for _ in 0..full_len {
-        grid_x5.push(vec![0; full_len])
+    let mut path = vec![map.pos(dst)];
+    let mut idx = dst;
+    while idx != start {
+        idx = prev[idx];
+        path.push(map.pos(idx));
     }
+    path.reverse();
 
-    for y in 0..tile_len {
-        for x in 0..tile_len {
-            grid_x5[y][x] = grid[y][x];
-        }
+    Some(Route { cost: costs[dst], path })
+}
+
+/// Print the map with the path's positions highlighted, either in bold with
+/// ANSI escape codes or replacing the rest of positions with dots
+fn render_route(map: &RiskMap, path: &[Pos], highlight: Highlight) -> String {
+    let mut in_path = vec![false; map.len()];
+    for &(x, y) in path {
+        in_path[y * map.width() + x] = true;
     }
 
-    for y in tile_len..full_len {
-        for x in 0..tile_len {
-            let mut val = grid_x5[y - tile_len][x] + 1;
-            if val > 9 { val = 1; }
-            grid_x5[y][x] = val;
+    let mut out = String::with_capacity(map.len() * 2);
+    for (idx, in_path) in in_path.into_iter().enumerate() {
+        let digit = (b'0' + map.risk(idx)) as char;
+        match (in_path, highlight) {
+            (true, Highlight::Ansi) => out.push_str(&format!("\x1b[1;97m{}\x1b[0m", digit)),
+            (true, Highlight::Dots) => out.push(digit),
+            (false, Highlight::Ansi) => out.push_str(&format!("\x1b[2m{}\x1b[0m", digit)),
+            (false, Highlight::Dots) => out.push('.'),
+        }
+        if (idx + 1) % map.width() == 0 {
+            out.push('\n');
         }
     }
+    out
+}
 
-    for y in 0..full_len {
-        for x in tile_len..full_len {
-            let mut val = grid_x5[y][x - tile_len] + 1;
-            if val > 9 { val = 1; }
-            grid_x5[y][x] = val;
-        }
+impl RiskMap {
+    fn new(rows: Vec<Vec<u8>>) -> Self {
+        let tile_height = rows.len();
+        let tile_width = rows.first().map_or(0, |row| row.len());
+        assert!(rows.iter().all(|row| row.len() == tile_width), "All rows must have the same length");
+        RiskMap { tile_width, tile_height, risks: rows.concat(), tiles: (1, 1) }
     }
 
-    grid_x5
-}
+    fn tiled(&self, tiles_x: usize, tiles_y: usize) -> Self {
+        RiskMap { tiles: (tiles_x, tiles_y), ..self.clone() }
+    }
 
-#[derive(Clone, Debug)]
-struct Node {
-    cost: u32,
-    pos: (usize, usize),
-    dst: (usize, usize),
-}
+    fn width(&self) -> usize {
+        self.tile_width * self.tiles.0
+    }
 
-impl Node {
-    fn estimation(&self) -> u32 {
-        self.cost + 2 * (self.dst.0 - self.pos.0 + self.dst.1 - self.pos.1) as u32
+    fn height(&self) -> usize {
+        self.tile_height * self.tiles.1
     }
 
-    fn best_final_cost(&self) -> u32 {
-        self.cost + (self.dst.0 - self.pos.0 + self.dst.1 - self.pos.1) as u32
+    fn len(&self) -> usize {
+        self.width() * self.height()
     }
 
-    fn adjacent_positions(&self) -> Vec<(usize, usize)> {
-        let mut positions = Vec::new();
-        if self.pos.0 > 0 { positions.push((self.pos.0 - 1, self.pos.1)); }
-        if self.pos.1 > 0 { positions.push((self.pos.0, self.pos.1 - 1)); }
-        if self.pos.0 < self.dst.0 { positions.push((self.pos.0 + 1, self.pos.1)); }
-        if self.pos.1 < self.dst.1 { positions.push((self.pos.0, self.pos.1 + 1)); }
-        positions
+    fn pos(&self, idx: usize) -> Pos {
+        (idx % self.width(), idx / self.width())
     }
-}
 
-impl Eq for Node {}
+    fn risk(&self, idx: usize) -> u8 {
+        let (x, y) = self.pos(idx);
+        let base = self.risks[(y % self.tile_height) * self.tile_width + x % self.tile_width];
+        let increment = x / self.tile_width + y / self.tile_height;
+        wrap_risk(base as usize + increment)
+    }
 
-impl PartialEq for Node {
-    fn eq(&self, other: &Self) -> bool {  // make min-heap instead of max-heap
-        self.estimation() == other.estimation()
+    fn min_risk(&self) -> u8 {
+        let max_increment = self.tiles.0 + self.tiles.1 - 2;
+        self.risks.iter()
+            .flat_map(|&r| (0..=max_increment.min(8)).map(move |inc| wrap_risk(r as usize + inc)))
+            .min()
+            .unwrap()
     }
-}
-impl Ord for Node {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.estimation().cmp(&self.estimation())
+
+    fn adjacent(&self, idx: usize, moves: Moves) -> impl Iterator<Item = usize> + '_ {
+        let offsets: &[(isize, isize)] = match moves {
+            Moves::Orthogonal => &ORTHOGONAL_MOVES,
+            Moves::Diagonal => &DIAGONAL_MOVES,
+            Moves::Knight => &KNIGHT_MOVES,
+        };
+        let (x, y) = self.pos(idx);
+        offsets.iter().filter_map(move |&(dx, dy)| {
+            let x = x.checked_add_signed(dx).filter(|x| *x < self.width())?;
+            let y = y.checked_add_signed(dy).filter(|y| *y < self.height())?;
+            Some(y * self.width() + x)
+        })
     }
 }
 
-impl PartialOrd for Node {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+fn wrap_risk(risk: usize) -> u8 {
+    ((risk - 1) % 9 + 1) as u8
 }
 
 fn parse_input(filename: &str) -> Vec<Vec<u8>> {
-    let f = File::open(filename).expect(&format!("Can't open {}", filename));
+    let f = File::open(filename).unwrap_or_else(|_| panic!("Can't open {}", filename));
     let reader = BufReader::new(f);
     reader.lines().map(|l| line_to_digits_vec(l.unwrap())).collect()
 }
@@ -139,3 +266,77 @@ fn parse_input(filename: &str) -> Vec<Vec<u8>> {
 fn line_to_digits_vec(line: String) -> Vec<u8> {
     line.chars().map(|c| c.to_digit(10).unwrap() as u8).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: [&str; 10] = [
+        "1163751742", "1381373672", "2136511328", "3694931569", "7463417111",
+        "1319128137", "1359912421", "3125421639", "1293138521", "2311944581",
+    ];
+
+    fn example_map() -> RiskMap {
+        RiskMap::new(EXAMPLE.iter().map(|l| line_to_digits_vec(l.to_string())).collect())
+    }
+
+    fn check_route(map: &RiskMap, route: &Route, moves: &[(isize, isize)]) {
+        assert_eq!(route.path.first(), Some(&(0, 0)));
+        assert_eq!(route.path.last(), Some(&(map.width() - 1, map.height() - 1)));
+
+        let path_cost: u32 = route.path[1..].iter().map(|&(x, y)| map.risk(y * map.width() + x) as u32).sum();
+        assert_eq!(path_cost, route.cost);
+        for pair in route.path.windows(2) {
+            let dx = pair[1].0 as isize - pair[0].0 as isize;
+            let dy = pair[1].1 as isize - pair[0].1 as isize;
+            assert!(moves.contains(&(dx, dy)));
+        }
+    }
+
+    #[test]
+    fn example_routes() {
+        let map = example_map();
+        let route = solve(&map, Moves::Orthogonal).unwrap();
+        assert_eq!(route.cost, 40);
+        check_route(&map, &route, &ORTHOGONAL_MOVES);
+
+        let map_x5 = map.tiled(5, 5);
+        let route = solve(&map_x5, Moves::Orthogonal).unwrap();
+        assert_eq!(route.cost, 315);
+        check_route(&map_x5, &route, &ORTHOGONAL_MOVES);
+    }
+
+    #[test]
+    fn lazy_tiles() {
+        let map = RiskMap::new(vec![vec![8, 9], vec![1, 2], vec![3, 4]]).tiled(3, 2);
+        assert_eq!((map.width(), map.height()), (6, 6));
+        let row = |y: usize| (0..6).map(|x| map.risk(y * 6 + x)).collect::<Vec<u8>>();
+        assert_eq!(row(0), [8, 9, 9, 1, 1, 2]);
+        assert_eq!(row(4), [2, 3, 3, 4, 4, 5]);
+        assert_eq!(map.min_risk(), 1);
+    }
+
+    #[test]
+    fn other_moves() {
+        let map = example_map().tiled(2, 3);
+        let orthogonal = solve(&map, Moves::Orthogonal).unwrap();
+
+        let diagonal = solve(&map, Moves::Diagonal).unwrap();
+        check_route(&map, &diagonal, &DIAGONAL_MOVES);
+        assert!(diagonal.cost <= orthogonal.cost);
+
+        let knight = solve(&map, Moves::Knight).unwrap();
+        check_route(&map, &knight, &KNIGHT_MOVES);
+
+        let map = RiskMap::new(vec![vec![1, 1, 1]]);
+        assert_eq!(solve(&map, Moves::Knight), None);
+    }
+
+    #[test]
+    fn render() {
+        let map = RiskMap::new(vec![vec![1, 9], vec![2, 3]]);
+        let route = solve(&map, Moves::Orthogonal).unwrap();
+        assert_eq!(route, Route { cost: 5, path: vec![(0, 0), (0, 1), (1, 1)] });
+        assert_eq!(render_route(&map, &route.path, Highlight::Dots), "1.\n23\n");
+    }
+}
@@ -0,0 +1,114 @@
+//! Fuzzer for the BITS decoder: mutates the transmissions of fuzz/corpus and
+//! checks that decoding, evaluating and re-encoding them never panics.
+//!
+//!     cargo run --release --example fuzz -- [ITERATIONS [SEED]]
+
+use day16::{check_transmission, Rng};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let iterations = args.first().map_or(Ok(100_000), |s| s.parse());
+    let seed = args.get(1).map_or(Ok(0x2021_1216), |s| s.parse());
+    match (iterations, seed) {
+        (Ok(iterations), Ok(seed)) if args.len() <= 2 => run_fuzzer(iterations, seed),
+        _ => {
+            eprintln!("Usage: fuzz [ITERATIONS [SEED]]");
+            std::process::exit(1);
+        },
+    }
+}
+
+fn run_fuzzer(iterations: usize, seed: u64) {
+    let corpus = read_fuzz_corpus();
+    println!("Fuzzing {} iterations from {} corpus entries, seed={}", iterations, corpus.len(), seed);
+
+    // Don't print every panic, only the input that caused it
+    std::panic::set_hook(Box::new(|_| {}));
+    let result = fuzz(&corpus, iterations, seed);
+    let _ = std::panic::take_hook();
+
+    if let Err((input, msg)) = result {
+        let path = std::env::temp_dir().join(format!("day16-crash-{}.hex", seed));
+        eprintln!("Panic: {}\nInput: {}", msg, input);
+        if std::fs::write(&path, &input).is_ok() {
+            eprintln!("Saved to {}", path.display());
+        }
+        std::process::exit(1);
+    }
+    println!("No panics found");
+}
+
+fn read_fuzz_corpus() -> Vec<String> {
+    let dir = format!("{}/fuzz/corpus", env!("CARGO_MANIFEST_DIR"));
+    let mut paths: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("Can't read {}: {}", dir, e))
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    paths.iter()
+        .map(|path| std::fs::read_to_string(path).unwrap().trim().to_string())
+        .collect()
+}
+
+/// Feed mutations of the corpus entries to the decoder, returning the first
+/// input that makes it panic and the panic's message
+fn fuzz(corpus: &[String], iterations: usize, seed: u64) -> Result<(), (String, String)> {
+    let mut rng = Rng(seed.max(1));
+    for _ in 0..iterations {
+        let base = &corpus[rng.next(corpus.len() as u64) as usize];
+        let input = mutate(base, &mut rng);
+        if let Err(e) = std::panic::catch_unwind(|| check_transmission(&input)) {
+            let msg = e.downcast_ref::<String>().cloned()
+                .or_else(|| e.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default();
+            return Err((input, msg));
+        }
+    }
+    Ok(())
+}
+
+fn mutate(input: &str, rng: &mut Rng) -> String {
+    const HEX: &[u8] = b"0123456789ABCDEF";
+    let mut bytes = input.as_bytes().to_vec();
+    for _ in 0..1 + rng.next(4) {
+        let pos = rng.next(bytes.len() as u64 + 1) as usize;
+        let random_hex = HEX[rng.next(16) as usize];
+        match rng.next(8) {
+            0 | 1 if pos < bytes.len() => {
+                // Flip a bit of a hex digit
+                if let Some(d) = (bytes[pos] as char).to_digit(16) {
+                    bytes[pos] = HEX[(d ^ (1 << rng.next(4))) as usize];
+                }
+            },
+            2 if pos < bytes.len() => bytes[pos] = random_hex,
+            3 => bytes.insert(pos, random_hex),
+            4 if pos < bytes.len() => { bytes.remove(pos); },
+            5 => bytes.truncate(pos),
+            6 => {
+                let end = pos + rng.next((bytes.len() - pos) as u64 + 1) as usize;
+                let chunk = bytes[pos..end].to_vec();
+                bytes.splice(pos..pos, chunk);
+            },
+            _ if rng.next(50) == 0 => bytes.insert(pos, b"g \n-"[rng.next(4) as usize]),
+            _ => bytes.push(random_hex),
+        }
+    }
+    String::from_utf8(bytes).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzz_corpus() {
+        let corpus = read_fuzz_corpus();
+        assert!(!corpus.is_empty());
+        for input in &corpus {
+            check_transmission(input);
+        }
+        if let Err((input, msg)) = fuzz(&corpus, 20_000, 1) {
+            panic!("Fuzzer found a panic with input {}: {}", input, msg);
+        }
+    }
+}
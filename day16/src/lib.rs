@@ -0,0 +1,1055 @@
+use std::fmt;
+
+const HDR_LEN: usize = 6;
+const MIN_PACKET_LEN: usize = HDR_LEN + 5;
+/// Max nesting of packets, to not overflow the stack with malicious inputs
+const MAX_DEPTH: usize = 1000;
+const LITERAL_TYPE_ID: u8 = 4;
+#[cfg(test)]
+const MAX_OP0_SUBPACKETS_LEN: usize = (1 << 15) - 1;
+#[cfg(test)]
+const MAX_OP1_SUBPACKETS_NUM: usize = (1 << 11) - 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Packet {
+    Literal(LiteralPacket),
+    Operator(OperatorPacket),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiteralPacket {
+    version: u8,
+    digits: Vec<u8>,
+    bits_len: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperatorPacket {
+    version: u8,
+    operator_id: u8,
+    subpackets: Vec<Packet>,
+    bits_len: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LengthType {
+    /// Length type 0: the total length in bits of the subpackets
+    Bits,
+    /// Length type 1: the number of subpackets
+    Count,
+}
+
+/// Builder for packets. Literals store the value's hex digits without leading
+/// zeros. Operators use the given length type, or the shortest valid one if
+/// not specified.
+#[cfg(test)]
+#[derive(Clone, Debug)]
+struct PacketBuilder {
+    version: u8,
+    kind: BuilderKind,
+}
+
+#[cfg(test)]
+#[derive(Clone, Debug)]
+enum BuilderKind {
+    Literal(Vec<u8>),
+    Operator { operator_id: u8, length_type: Option<LengthType>, subpackets: Vec<Packet> },
+}
+
+/// Transmission decoded from hex, as bytes with the bits MSB first. The last
+/// byte is half used if the number of hex digits is odd.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bits {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+/// Cursor to read fields of up to 64 bits. Reads can't go beyond `limit`,
+/// that is set to the end of the subpackets while reading an operator with
+/// length type 0.
+struct BitReader<'a> {
+    bits: &'a Bits,
+    pos: usize,
+    limit: usize,
+}
+
+pub fn sum_versions(packets: &[Packet]) -> u32 {
+    let mut sum = 0;
+    for pkt in packets {
+        sum += pkt.version() as u32;
+        if let Packet::Operator(pkt) = pkt {
+            sum += sum_versions(&pkt.subpackets);
+        }
+    }
+    sum
+}
+
+/// Parse all the packets of the transmission, that can be followed by padding
+/// bits, all of them zeros
+pub fn parse_all_packets(bits: &Bits) -> Result<Vec<Packet>, ParseError> {
+    let mut reader = BitReader::new(bits);
+    let mut packets = Vec::new();
+    while !reader.rest_is_zero() {
+        if reader.remaining() < MIN_PACKET_LEN {
+            return Err(ParseError::NonZeroPadding { offset: reader.pos });
+        }
+        packets.push(parse_next_packet(&mut reader)?);
+    }
+    Ok(packets)
+}
+
+fn parse_next_packet(reader: &mut BitReader) -> Result<Packet, ParseError> {
+    parse_packet(reader, 0)
+}
+
+fn parse_packet(reader: &mut BitReader, depth: usize) -> Result<Packet, ParseError> {
+    let offset = reader.pos;
+    if depth > MAX_DEPTH {
+        return Err(ParseError::TooDeep { offset });
+    }
+
+    let version = reader.read(3, offset)? as u8;
+    let type_id = reader.read(3, offset)? as u8;
+
+    if type_id == LITERAL_TYPE_ID {
+        let mut digits = Vec::new();
+        loop {
+            let next = reader.read(1, offset)?;
+            digits.push(reader.read(4, offset)? as u8);
+            if next == 0 {
+                break;
+            }
+        }
+        let bits_len = reader.pos - offset;
+        return Ok(Packet::Literal(LiteralPacket { version, digits, bits_len }));
+    }
+
+    let mut subpackets = Vec::new();
+    if reader.read(1, offset)? == 0 {
+        let subpackets_len = reader.read(15, offset)? as usize;
+        let end = reader.pos + subpackets_len;
+        if end > reader.limit {
+            return Err(ParseError::Truncated { offset });
+        }
+        let parent_limit = std::mem::replace(&mut reader.limit, end);
+        while reader.pos < end {
+            subpackets.push(parse_packet(reader, depth + 1)?);
+        }
+        reader.limit = parent_limit;
+    } else {
+        let subpackets_num = reader.read(11, offset)?;
+        for _ in 0..subpackets_num {
+            subpackets.push(parse_packet(reader, depth + 1)?);
+        }
+    }
+
+    let bits_len = reader.pos - offset;
+    Ok(Packet::Operator(OperatorPacket { version, operator_id: type_id, subpackets, bits_len }))
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bits: &'a Bits) -> Self {
+        BitReader { bits, pos: 0, limit: bits.len }
+    }
+
+    fn remaining(&self) -> usize {
+        self.limit - self.pos
+    }
+
+    fn rest_is_zero(&self) -> bool {
+        (self.pos..self.limit).all(|i| self.bits.bytes[i / 8] & (0x80 >> (i % 8)) == 0)
+    }
+
+    /// Read a field of `len` bits, or fail with a truncated packet error for
+    /// the packet that starts at `pkt_offset`
+    fn read(&mut self, len: usize, pkt_offset: usize) -> Result<u64, ParseError> {
+        debug_assert!(len <= 64);
+        if len > self.remaining() {
+            return Err(ParseError::Truncated { offset: pkt_offset });
+        }
+
+        let mut value = 0u64;
+        let mut len = len;
+        while len > 0 {
+            let byte = self.bits.bytes[self.pos / 8];
+            let used = self.pos % 8;
+            let take = len.min(8 - used);
+            let chunk = (byte << used) >> (8 - take);
+            value = (value << take) | chunk as u64;
+            self.pos += take;
+            len -= take;
+        }
+        Ok(value)
+    }
+}
+
+impl Packet {
+    fn version(&self) -> u8 {
+        match self {
+            Packet::Literal(p) => p.version,
+            Packet::Operator(p) => p.version,
+        }
+    }
+
+    fn bits_len(&self) -> usize {
+        match self {
+            Packet::Literal(p) => p.bits_len,
+            Packet::Operator(p) => p.bits_len,
+        }
+    }
+
+    /// Value of the packet, failing if any operation overflows u64
+    pub fn value(&self) -> Result<u64, EvalError> {
+        self.eval(0)
+    }
+
+    /// Value of the packet with arbitrary precision
+    pub fn big_value(&self) -> Result<BigUint, EvalError> {
+        self.eval(0)
+    }
+
+    /// Evaluate the packet, that starts at bit `offset` of the transmission,
+    /// so errors can report where the offending packet is
+    fn eval<T: EvalValue>(&self, offset: usize) -> Result<T, EvalError> {
+        match self {
+            Packet::Literal(p) => T::from_digits(&p.digits).ok_or(EvalError::Overflow { offset }),
+            Packet::Operator(p) => p.eval(offset),
+        }
+    }
+}
+
+impl LiteralPacket {
+    fn value(&self) -> BigUint {
+        BigUint::from_digits(&self.digits).unwrap()
+    }
+}
+
+impl OperatorPacket {
+    fn eval<T: EvalValue>(&self, offset: usize) -> Result<T, EvalError> {
+        let operator_id = self.operator_id;
+        let arity_ok = match operator_id {
+            0..=3 => !self.subpackets.is_empty(),
+            5..=7 => self.subpackets.len() == 2,
+            _ => return Err(EvalError::UnknownOperator { offset, operator_id }),
+        };
+        if !arity_ok {
+            return Err(EvalError::Arity { offset, operator_id, args: self.subpackets.len() });
+        }
+
+        let mut values = Vec::with_capacity(self.subpackets.len());
+        let mut subpacket_offset = offset + self.header_len();
+        for subpacket in &self.subpackets {
+            values.push(subpacket.eval::<T>(subpacket_offset)?);
+            subpacket_offset += subpacket.bits_len();
+        }
+
+        let overflow = || EvalError::Overflow { offset };
+        let value = match operator_id {
+            0 => values.into_iter().try_fold(T::zero(), |acc, v| acc.checked_add(&v)).ok_or_else(overflow)?,
+            1 => values.into_iter().try_fold(T::one(), |acc, v| acc.checked_mul(&v)).ok_or_else(overflow)?,
+            2 => values.into_iter().min().unwrap(),
+            3 => values.into_iter().max().unwrap(),
+            5 => if values[0] > values[1] { T::one() } else { T::zero() },
+            6 => if values[0] < values[1] { T::one() } else { T::zero() },
+            _ => if values[0] == values[1] { T::one() } else { T::zero() },
+        };
+        Ok(value)
+    }
+
+    /// Length of the header, before the subpackets
+    fn header_len(&self) -> usize {
+        self.bits_len - self.subpackets.iter().map(|p| p.bits_len()).sum::<usize>()
+    }
+}
+
+/// Numbers that packets can be evaluated to: `u64` with overflow checks, or
+/// `BigUint` that never overflows
+trait EvalValue: Sized + Ord {
+    fn zero() -> Self;
+    fn one() -> Self;
+    /// Number from hex digits, most significant first
+    fn from_digits(digits: &[u8]) -> Option<Self>;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+}
+
+impl EvalValue for u64 {
+    fn zero() -> Self { 0 }
+    fn one() -> Self { 1 }
+
+    fn from_digits(digits: &[u8]) -> Option<Self> {
+        digits.iter().try_fold(0u64, |acc, v| acc.checked_mul(16).map(|acc| acc | *v as u64))
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> { u64::checked_add(*self, *other) }
+    fn checked_mul(&self, other: &Self) -> Option<Self> { u64::checked_mul(*self, *other) }
+}
+
+impl EvalValue for BigUint {
+    fn zero() -> Self { BigUint::normalized(Vec::new()) }
+    fn one() -> Self { BigUint::normalized(vec![1]) }
+
+    fn from_digits(digits: &[u8]) -> Option<Self> {
+        let mut limbs = Vec::with_capacity(digits.len().div_ceil(8));
+        for chunk in digits.rchunks(8) {
+            limbs.push(chunk.iter().fold(0u32, |acc, v| (acc << 4) | *v as u32));
+        }
+        Some(BigUint::normalized(limbs))
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> { Some(self + other) }
+    fn checked_mul(&self, other: &Self) -> Option<Self> { Some(self * other) }
+}
+
+/// Unsigned integer of arbitrary size, with base 2^32 little endian digits and
+/// no leading zeros
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigUint {
+    digits: Vec<u32>,
+}
+
+impl BigUint {
+    fn normalized(mut digits: Vec<u32>) -> Self {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        BigUint { digits }
+    }
+}
+
+impl std::ops::Add for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let len = self.digits.len().max(other.digits.len());
+        let mut digits = Vec::with_capacity(len + 1);
+        let mut carry = 0u64;
+        for i in 0..len {
+            let a = *self.digits.get(i).unwrap_or(&0) as u64;
+            let b = *other.digits.get(i).unwrap_or(&0) as u64;
+            let sum = a + b + carry;
+            digits.push(sum as u32);
+            carry = sum >> 32;
+        }
+        digits.push(carry as u32);
+        BigUint::normalized(digits)
+    }
+}
+
+impl std::ops::Mul for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        let mut digits = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, a) in self.digits.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.digits.iter().enumerate() {
+                let v = digits[i + j] as u64 + *a as u64 * *b as u64 + carry;
+                digits[i + j] = v as u32;
+                carry = v >> 32;
+            }
+            digits[i + other.digits.len()] = carry as u32;
+        }
+        BigUint::normalized(digits)
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.digits.len().cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Divide repeatedly by 10^9 to get the decimal digits in groups of 9
+        let mut groups = Vec::new();
+        let mut digits = self.digits.clone();
+        while !digits.is_empty() {
+            let mut rem = 0u64;
+            for d in digits.iter_mut().rev() {
+                let v = (rem << 32) | *d as u64;
+                *d = (v / 1_000_000_000) as u32;
+                rem = v % 1_000_000_000;
+            }
+            groups.push(rem as u32);
+            digits = BigUint::normalized(digits).digits;
+        }
+
+        match groups.pop() {
+            None => write!(f, "0"),
+            Some(first) => {
+                write!(f, "{}", first)?;
+                for group in groups.iter().rev() {
+                    write!(f, "{:09}", group)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl PacketBuilder {
+    fn literal(value: u64) -> Self {
+        let mut digits = Vec::new();
+        let mut value = value;
+        loop {
+            digits.push((value & 0xF) as u8);
+            value >>= 4;
+            if value == 0 {
+                break;
+            }
+        }
+        digits.reverse();
+        PacketBuilder::literal_digits(digits)
+    }
+
+    fn literal_digits(digits: Vec<u8>) -> Self {
+        assert!(!digits.is_empty() && digits.iter().all(|d| *d <= 0xF), "Invalid literal digits");
+        PacketBuilder { version: 0, kind: BuilderKind::Literal(digits) }
+    }
+
+    fn operator(operator_id: u8) -> Self {
+        assert!(operator_id <= 7 && operator_id != LITERAL_TYPE_ID, "Invalid operator id {}", operator_id);
+        PacketBuilder {
+            version: 0,
+            kind: BuilderKind::Operator { operator_id, length_type: None, subpackets: Vec::new() },
+        }
+    }
+
+    fn version(mut self, version: u8) -> Self {
+        assert!(version <= 7, "Invalid version {}", version);
+        self.version = version;
+        self
+    }
+
+    fn length_type(mut self, length_type: LengthType) -> Self {
+        match &mut self.kind {
+            BuilderKind::Operator { length_type: lt, .. } => *lt = Some(length_type),
+            BuilderKind::Literal(_) => panic!("Literal packets don't have length type"),
+        }
+        self
+    }
+
+    fn subpacket(mut self, packet: Packet) -> Self {
+        match &mut self.kind {
+            BuilderKind::Operator { subpackets, .. } => subpackets.push(packet),
+            BuilderKind::Literal(_) => panic!("Literal packets don't have subpackets"),
+        }
+        self
+    }
+
+    fn build(self) -> Packet {
+        match self.kind {
+            BuilderKind::Literal(digits) => {
+                let bits_len = HDR_LEN + 5 * digits.len();
+                Packet::Literal(LiteralPacket { version: self.version, digits, bits_len })
+            },
+            BuilderKind::Operator { operator_id, length_type, subpackets } => {
+                let subpackets_len: usize = subpackets.iter().map(|p| p.bits_len()).sum();
+                let fits_count = subpackets.len() <= MAX_OP1_SUBPACKETS_NUM;
+                let fits_bits = subpackets_len <= MAX_OP0_SUBPACKETS_LEN;
+                let length_type = match length_type {
+                    Some(LengthType::Bits) => { assert!(fits_bits, "Subpackets too long"); LengthType::Bits },
+                    Some(LengthType::Count) => { assert!(fits_count, "Too many subpackets"); LengthType::Count },
+                    None if fits_count => LengthType::Count,
+                    None => { assert!(fits_bits, "Subpackets too long and too many"); LengthType::Bits },
+                };
+                let bits_len = match length_type {
+                    LengthType::Bits => 22 + subpackets_len,
+                    LengthType::Count => 18 + subpackets_len,
+                };
+                Packet::Operator(OperatorPacket { version: self.version, operator_id, subpackets, bits_len })
+            },
+        }
+    }
+}
+
+impl OperatorPacket {
+    /// The length type is not stored, but it determines the packet's length
+    fn length_type(&self) -> LengthType {
+        let subpackets_len: usize = self.subpackets.iter().map(|p| p.bits_len()).sum();
+        if self.bits_len == 22 + subpackets_len { LengthType::Bits } else { LengthType::Count }
+    }
+}
+
+/// Disassembly of every packet of the transmission, each followed by its
+/// expression and value
+pub fn dump(packets: &[Packet]) -> String {
+    let mut listing = String::new();
+    let mut offset = 0;
+    for pkt in packets {
+        listing.push_str(&disassemble(pkt, offset));
+        match pkt.eval::<BigUint>(offset) {
+            Ok(value) => listing.push_str(&format!("{} = {}\n", expression(pkt), value)),
+            Err(e) => listing.push_str(&format!("{} = Error: {}\n", expression(pkt), e)),
+        }
+        offset += pkt.bits_len();
+    }
+    listing
+}
+
+fn operator_name(operator_id: u8) -> &'static str {
+    match operator_id {
+        0 => "sum",
+        1 => "product",
+        2 => "min",
+        3 => "max",
+        5 => "gt",
+        6 => "lt",
+        7 => "eq",
+        _ => "unknown",
+    }
+}
+
+/// Listing of the packet tree, one packet per line indented by depth, with
+/// the bit offset where each packet starts
+fn disassemble(packet: &Packet, offset: usize) -> String {
+    let mut listing = String::new();
+    disassemble_into(packet, offset, 0, &mut listing);
+    listing
+}
+
+fn disassemble_into(packet: &Packet, offset: usize, depth: usize, listing: &mut String) {
+    let indent = "  ".repeat(depth);
+    match packet {
+        Packet::Literal(p) => {
+            listing.push_str(&format!(
+                "{:>6}: {}v{} type={} literal {} ({} bits)\n",
+                offset, indent, p.version, LITERAL_TYPE_ID, p.value(), p.bits_len
+            ));
+        },
+        Packet::Operator(p) => {
+            let (length_type, length) = match p.length_type() {
+                LengthType::Bits => (0, format!("subpackets_len={}", p.bits_len - 22)),
+                LengthType::Count => (1, format!("subpackets_num={}", p.subpackets.len())),
+            };
+            listing.push_str(&format!(
+                "{:>6}: {}v{} type={} {} length_type={} {} ({} bits)\n",
+                offset, indent, p.version, p.operator_id, operator_name(p.operator_id),
+                length_type, length, p.bits_len
+            ));
+
+            let mut offset = offset + p.header_len();
+            for subpacket in &p.subpackets {
+                disassemble_into(subpacket, offset, depth + 1, listing);
+                offset += subpacket.bits_len();
+            }
+        },
+    }
+}
+
+/// Readable expression of the packet, like `(1 + 3) == (2 * 2)`
+fn expression(packet: &Packet) -> String {
+    let expr = subexpression(packet);
+    match packet {
+        Packet::Operator(p) if is_infix(p) && p.subpackets.len() > 1 => expr[1..expr.len() - 1].to_string(),
+        _ => expr,
+    }
+}
+
+fn subexpression(packet: &Packet) -> String {
+    let p = match packet {
+        Packet::Literal(p) => return p.value().to_string(),
+        Packet::Operator(p) => p,
+    };
+
+    let args: Vec<String> = p.subpackets.iter().map(subexpression).collect();
+    let symbol = match p.operator_id {
+        0 => " + ",
+        1 => " * ",
+        5 => " > ",
+        6 => " < ",
+        7 => " == ",
+        _ => return format!("{}({})", operator_name(p.operator_id), args.join(", ")),
+    };
+
+    if args.len() == 1 {
+        args.into_iter().next().unwrap()
+    } else {
+        format!("({})", args.join(symbol))
+    }
+}
+
+fn is_infix(packet: &OperatorPacket) -> bool {
+    matches!(packet.operator_id, 0 | 1 | 5 | 6 | 7)
+}
+
+/// Serialize packets to the hex transmission format, padded with zeros to a
+/// whole number of hex digits
+fn encode_packets(packets: &[Packet]) -> String {
+    let mut writer = BitWriter::default();
+    for pkt in packets {
+        encode_packet(pkt, &mut writer);
+    }
+    writer.to_hex()
+}
+
+fn encode_packet(packet: &Packet, writer: &mut BitWriter) {
+    match packet {
+        Packet::Literal(p) => {
+            writer.push(p.version as u64, 3);
+            writer.push(LITERAL_TYPE_ID as u64, 3);
+            for (i, digit) in p.digits.iter().enumerate() {
+                let next = if i + 1 < p.digits.len() { 1 } else { 0 };
+                writer.push(next, 1);
+                writer.push(*digit as u64, 4);
+            }
+        },
+        Packet::Operator(p) => {
+            writer.push(p.version as u64, 3);
+            writer.push(p.operator_id as u64, 3);
+            match p.length_type() {
+                LengthType::Bits => {
+                    writer.push(0, 1);
+                    writer.push((p.bits_len - 22) as u64, 15);
+                },
+                LengthType::Count => {
+                    writer.push(1, 1);
+                    writer.push(p.subpackets.len() as u64, 11);
+                },
+            }
+            for subpacket in &p.subpackets {
+                encode_packet(subpacket, writer);
+            }
+        },
+    }
+}
+
+/// xorshift64, to generate random inputs without external crates. The seed
+/// must not be 0.
+pub struct Rng(pub u64);
+
+impl Rng {
+    /// Random number in `0..max`
+    pub fn next(&mut self, max: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % max
+    }
+}
+
+/// Run everything that can be done with a transmission, for the fuzzer.
+/// Errors are fine, but it must not panic, and packets that are decoded must
+/// encode back to the same packets.
+pub fn check_transmission(input: &str) {
+    let Ok(bits) = parse_input(input) else {
+        return;
+    };
+    let Ok(packets) = parse_all_packets(&bits) else {
+        return;
+    };
+
+    let mut offset = 0;
+    for pkt in &packets {
+        let _ = pkt.eval::<u64>(offset);
+        let _ = pkt.eval::<BigUint>(offset).map(|v| v.to_string());
+        let _ = disassemble(pkt, offset);
+        let _ = expression(pkt);
+        offset += pkt.bits_len();
+    }
+
+    let encoded = encode_packets(&packets);
+    let decoded = parse_input(&encoded).and_then(|bits| parse_all_packets(&bits));
+    assert_eq!(decoded.as_ref(), Ok(&packets), "Round trip mismatch, encoded={}", encoded);
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bits: Bits,
+}
+
+impl BitWriter {
+    /// Append the `len` least significant bits of `value`, MSB first
+    fn push(&mut self, value: u64, len: usize) {
+        for i in (0..len).rev() {
+            if self.bits.len.is_multiple_of(8) {
+                self.bits.bytes.push(0);
+            }
+            let bit = ((value >> i) & 1) as u8;
+            *self.bits.bytes.last_mut().unwrap() |= bit << (7 - self.bits.len % 8);
+            self.bits.len += 1;
+        }
+    }
+
+    fn to_hex(&self) -> String {
+        let mut hex: String = self.bits.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        hex.truncate(self.bits.len.div_ceil(4));
+        hex
+    }
+}
+
+pub fn parse_input(input: &str) -> Result<Bits, ParseError> {
+    let mut bits = Bits { bytes: Vec::with_capacity(input.len().div_ceil(2)), len: 4 * input.len() };
+    for (index, ch) in input.chars().enumerate() {
+        let digit = ch.to_digit(16).ok_or(ParseError::InvalidHex { index, ch })? as u8;
+        if index % 2 == 0 {
+            bits.bytes.push(digit << 4);
+        } else {
+            *bits.bytes.last_mut().unwrap() |= digit;
+        }
+    }
+    Ok(bits)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalError {
+    /// The value of the packet at bit `offset` doesn't fit
+    Overflow { offset: usize },
+    /// Operator packet at bit `offset` with a wrong number of subpackets
+    Arity { offset: usize, operator_id: u8, args: usize },
+    /// Operator packet at bit `offset` with an id that is not an operator
+    UnknownOperator { offset: usize, operator_id: u8 },
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::Overflow { offset } => write!(f, "Overflow in packet at bit {}", offset),
+            EvalError::Arity { offset, operator_id, args } => write!(
+                f, "Operator {} ({}) with {} subpackets at bit {}",
+                operator_id, operator_name(*operator_id), args, offset
+            ),
+            EvalError::UnknownOperator { offset, operator_id } => {
+                write!(f, "Unknown operator {} at bit {}", operator_id, offset)
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    /// Character at `index` is not a hex digit
+    InvalidHex { index: usize, ch: char },
+    /// Packet at bit `offset` goes beyond the end of the transmission, or of
+    /// the subpackets length of its parent
+    Truncated { offset: usize },
+    /// Non zero bits after the last packet, from bit `offset`
+    NonZeroPadding { offset: usize },
+    /// Packet at bit `offset` nested too deep
+    TooDeep { offset: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidHex { index, ch } => write!(f, "Invalid hex digit '{}' at {}", ch, index),
+            ParseError::Truncated { offset } => write!(f, "Truncated packet at bit {}", offset),
+            ParseError::NonZeroPadding { offset } => write!(f, "Non zero padding at bit {}", offset),
+            ParseError::TooDeep { offset } => write!(f, "Packet nested too deep at bit {}", offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let bits = parse_input("D2FE28").unwrap();
+        assert_eq!(bits, Bits { bytes: vec![0xD2, 0xFE, 0x28], len: 24 });
+        let bits = parse_input("38006F4529120").unwrap();
+        assert_eq!(bits, Bits { bytes: vec![0x38, 0x00, 0x6F, 0x45, 0x29, 0x12, 0x00], len: 52 });
+        assert_eq!(parse_input("38G0"), Err(ParseError::InvalidHex { index: 2, ch: 'G' }));
+    }
+
+    #[test]
+    fn literal_packet() {
+        let bits = parse_input("D2FE28AA").unwrap();
+        let packet = parse_next_packet(&mut BitReader::new(&bits)).unwrap();
+
+        let expected_packet = Packet::Literal(LiteralPacket {
+            version: 6, digits: vec![7, 14, 5], bits_len: 21,
+        });
+        assert_eq!(packet, expected_packet);
+        assert_eq!(packet.value(), Ok(2021));
+    }
+
+    #[test]
+    fn operator_0_packet() {
+        let bits = parse_input("38006F45291200").unwrap();
+        let packet = parse_next_packet(&mut BitReader::new(&bits)).unwrap();
+
+        let expected_packet = Packet::Operator(OperatorPacket {
+            version: 1,
+            operator_id: 6,
+            subpackets: vec![
+                Packet::Literal(LiteralPacket { version: 6, digits: vec![10], bits_len: 11 }),
+                Packet::Literal(LiteralPacket { version: 2, digits: vec![1, 4], bits_len: 16 }),
+            ],
+            bits_len: 49,
+        });
+        assert_eq!(packet, expected_packet);
+    }
+
+    #[test]
+    fn operator_1_packet() {
+        let bits = parse_input("EE00D40C823060").unwrap();
+        let packet = parse_next_packet(&mut BitReader::new(&bits)).unwrap();
+
+        let expected_packet = Packet::Operator(OperatorPacket {
+            version: 7,
+            operator_id: 3,
+            subpackets: vec![
+                Packet::Literal(LiteralPacket { version: 2, digits: vec![1], bits_len: 11 }),
+                Packet::Literal(LiteralPacket { version: 4, digits: vec![2], bits_len: 11 }),
+                Packet::Literal(LiteralPacket { version: 1, digits: vec![3], bits_len: 11 }),
+            ],
+            bits_len: 51,
+        });
+        assert_eq!(packet, expected_packet);
+    }
+
+    // C200B40A82 finds the sum of 1 and 2, resulting in the value 3.
+    // 04005AC33890 finds the product of 6 and 9, resulting in the value 54.
+    // 880086C3E88112 finds the minimum of 7, 8, and 9, resulting in the value 7.
+    // CE00C43D881120 finds the maximum of 7, 8, and 9, resulting in the value 9.
+    // D8005AC2A8F0 produces 1, because 5 is less than 15.
+    // F600BC2D8F produces 0, because 5 is not greater than 15.
+    // 9C005AC2F8F0 produces 0, because 5 is not equal to 15.
+    // 9C0141080250320F1802104A08 produces 1, because 1 + 3 = 2 * 2
+
+    #[test]
+    fn sum() {
+        let bits = parse_input("C200B40A82").unwrap();
+        let packets = parse_all_packets(&bits).unwrap();
+        assert_eq!(packets[0].value(), Ok(3));
+    }
+
+    #[test]
+    fn mult() {
+        let bits = parse_input("04005AC33890").unwrap();
+        let packets = parse_all_packets(&bits).unwrap();
+        assert_eq!(packets[0].value(), Ok(54));
+    }
+
+    #[test]
+    fn min() {
+        let bits = parse_input("880086C3E88112").unwrap();
+        let packets = parse_all_packets(&bits).unwrap();
+        assert_eq!(packets[0].value(), Ok(7));
+    }
+
+    #[test]
+    fn max() {
+        let bits = parse_input("CE00C43D881120").unwrap();
+        let packets = parse_all_packets(&bits).unwrap();
+        assert_eq!(packets[0].value(), Ok(9));
+    }
+
+    #[test]
+    fn lt() {
+        let bits = parse_input("D8005AC2A8F0").unwrap();
+        let packets = parse_all_packets(&bits).unwrap();
+        assert_eq!(packets[0].value(), Ok(1));
+    }
+
+    #[test]
+    fn gt() {
+        let bits = parse_input("F600BC2D8F").unwrap();
+        let packets = parse_all_packets(&bits).unwrap();
+        assert_eq!(packets[0].value(), Ok(0));
+    }
+
+    #[test]
+    fn eq() {
+        let bits = parse_input("9C005AC2F8F0").unwrap();
+        let packets = parse_all_packets(&bits).unwrap();
+        assert_eq!(packets[0].value(), Ok(0));
+    }
+
+    #[test]
+    fn sum_mult_eq() {
+        let bits = parse_input("9C0141080250320F1802104A08").unwrap();
+        let packets = parse_all_packets(&bits).unwrap();
+        assert_eq!(packets[0].value(), Ok(1));
+    }
+
+    #[test]
+    fn encode_examples() {
+        let examples = ["D2FE28", "38006F45291200", "EE00D40C823060", "C200B40A82", "9C0141080250320F1802104A08"];
+        for hex in examples {
+            let packets = parse_all_packets(&parse_input(hex).unwrap()).unwrap();
+            let encoded = encode_packets(&packets);
+            assert_eq!(parse_all_packets(&parse_input(&encoded).unwrap()).unwrap(), packets);
+            assert!(hex.starts_with(encoded.trim_end_matches('0')));
+        }
+    }
+
+    #[test]
+    fn builder() {
+        let packet = PacketBuilder::operator(6).version(1).length_type(LengthType::Bits)
+            .subpacket(PacketBuilder::literal(10).version(6).build())
+            .subpacket(PacketBuilder::literal(20).version(2).build())
+            .build();
+        assert_eq!(encode_packets(std::slice::from_ref(&packet)), "38006F4529120");
+        assert_eq!(packet.value(), Ok(1));
+
+        let packet = PacketBuilder::operator(3).version(7)
+            .subpacket(PacketBuilder::literal(1).version(2).build())
+            .subpacket(PacketBuilder::literal(2).version(4).build())
+            .subpacket(PacketBuilder::literal(3).version(1).build())
+            .build();
+        assert_eq!(encode_packets(&[packet]), "EE00D40C82306");
+    }
+
+    #[test]
+    fn parse_errors() {
+        // Literal with the last group cut
+        let bits = parse_input("D2FE").unwrap();
+        assert_eq!(parse_all_packets(&bits), Err(ParseError::Truncated { offset: 0 }));
+
+        // Operator with 2 subpackets, but the subpackets length cuts the 2nd
+        let mut writer = BitWriter::default();
+        for (value, len) in [(1, 3), (6, 3), (0, 1), (20, 15), (6, 3), (4, 3), (10, 5), (2, 3), (4, 3), (0x24, 10)] {
+            writer.push(value, len);
+        }
+        let bits = parse_input(&writer.to_hex()).unwrap();
+        assert_eq!(parse_all_packets(&bits), Err(ParseError::Truncated { offset: 33 }));
+
+        let bits = parse_input("D2FE2A").unwrap();
+        assert_eq!(parse_all_packets(&bits), Err(ParseError::NonZeroPadding { offset: 21 }));
+    }
+
+    #[test]
+    fn long_transmission() {
+        let mut builder = PacketBuilder::operator(0);
+        for i in 0..2000 {
+            builder = builder.subpacket(PacketBuilder::literal(i).build());
+        }
+        let packet = builder.build();
+        let bits = parse_input(&encode_packets(std::slice::from_ref(&packet))).unwrap();
+        assert_eq!(parse_all_packets(&bits).unwrap(), vec![packet]);
+    }
+
+    #[test]
+    fn disassembler() {
+        let bits = parse_input("38006F45291200").unwrap();
+        let packets = parse_all_packets(&bits).unwrap();
+        assert_eq!(
+            disassemble(&packets[0], 0),
+            "     0: v1 type=6 lt length_type=0 subpackets_len=27 (49 bits)\n\
+            \x20   22:   v6 type=4 literal 10 (11 bits)\n\
+            \x20   33:   v2 type=4 literal 20 (16 bits)\n"
+        );
+
+        let bits = parse_input("EE00D40C823060").unwrap();
+        let packets = parse_all_packets(&bits).unwrap();
+        let listing = disassemble(&packets[0], 0);
+        assert!(listing.starts_with("     0: v7 type=3 max length_type=1 subpackets_num=3 (51 bits)\n"));
+        assert!(listing.ends_with("    40:   v1 type=4 literal 3 (11 bits)\n"));
+    }
+
+    #[test]
+    fn expressions() {
+        let examples = [
+            ("C200B40A82", "1 + 2"),
+            ("04005AC33890", "6 * 9"),
+            ("880086C3E88112", "min(7, 8, 9)"),
+            ("CE00C43D881120", "max(7, 8, 9)"),
+            ("D8005AC2A8F0", "5 < 15"),
+            ("9C0141080250320F1802104A08", "(1 + 3) == (2 * 2)"),
+        ];
+        for (hex, expected) in examples {
+            let packets = parse_all_packets(&parse_input(hex).unwrap()).unwrap();
+            assert_eq!(expression(&packets[0]), expected);
+        }
+    }
+
+    #[test]
+    fn eval_overflow() {
+        // 17 hex digits literal
+        let literal = PacketBuilder::literal_digits(vec![1; 17]).build();
+        assert_eq!(literal.value(), Err(EvalError::Overflow { offset: 0 }));
+        assert_eq!(literal.big_value().unwrap().to_string(), "19676527011956855057");
+
+        let big = PacketBuilder::literal(u64::MAX).build();
+        let packet = PacketBuilder::operator(3)
+            .subpacket(PacketBuilder::literal(1).build())
+            .subpacket(PacketBuilder::operator(1).subpacket(big.clone()).subpacket(big.clone()).build())
+            .build();
+        assert_eq!(packet.value(), Err(EvalError::Overflow { offset: 29 }));
+        assert_eq!(packet.big_value().unwrap().to_string(), "340282366920938463426481119284349108225");
+
+        let packet = PacketBuilder::operator(0).subpacket(big.clone()).subpacket(big).build();
+        assert_eq!(packet.value(), Err(EvalError::Overflow { offset: 0 }));
+    }
+
+    #[test]
+    fn eval_errors() {
+        let one = PacketBuilder::literal(1).build();
+        let packet = PacketBuilder::operator(0)
+            .subpacket(one.clone())
+            .subpacket(PacketBuilder::operator(5).subpacket(one.clone()).build())
+            .build();
+        assert_eq!(packet.value(), Err(EvalError::Arity { offset: 29, operator_id: 5, args: 1 }));
+
+        let packet = PacketBuilder::operator(2).build();
+        assert_eq!(packet.value(), Err(EvalError::Arity { offset: 0, operator_id: 2, args: 0 }));
+
+        let packet = Packet::Operator(OperatorPacket {
+            version: 0, operator_id: 4, subpackets: vec![one], bits_len: 29,
+        });
+        assert_eq!(packet.value(), Err(EvalError::UnknownOperator { offset: 0, operator_id: 4 }));
+    }
+
+    fn random_packet(rng: &mut Rng, depth: usize) -> Packet {
+        let version = rng.next(8) as u8;
+        if depth == 0 || rng.next(3) == 0 {
+            let digits = (0..1 + rng.next(20)).map(|_| rng.next(16) as u8).collect();
+            return PacketBuilder::literal_digits(digits).version(version).build();
+        }
+
+        let mut builder = PacketBuilder::operator([0, 1, 2, 3, 5, 6, 7][rng.next(7) as usize]).version(version);
+        if rng.next(2) == 0 {
+            builder = builder.length_type(LengthType::Bits);
+        }
+        for _ in 0..1 + rng.next(4) {
+            builder = builder.subpacket(random_packet(rng, depth - 1));
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let mut rng = Rng(0x2021_1216);
+        for _ in 0..500 {
+            let packet = random_packet(&mut rng, 4);
+            let encoded = encode_packets(std::slice::from_ref(&packet));
+            let decoded = parse_all_packets(&parse_input(&encoded).unwrap()).unwrap();
+            assert_eq!(decoded, vec![packet]);
+        }
+    }
+
+    #[test]
+    fn too_deep() {
+        let mut writer = BitWriter::default();
+        for _ in 0..100_000 {
+            for (value, len) in [(1, 3), (0, 3), (1, 1), (1, 11)] {
+                writer.push(value, len);
+            }
+        }
+        writer.push(0b10000001, 11);
+        let bits = parse_input(&writer.to_hex()).unwrap();
+        let offset = 18 * (MAX_DEPTH + 1);
+        assert_eq!(parse_all_packets(&bits), Err(ParseError::TooDeep { offset }));
+    }
+
+    #[test]
+    fn corpus_transmissions() {
+        let dir = format!("{}/fuzz/corpus", env!("CARGO_MANIFEST_DIR"));
+        for entry in std::fs::read_dir(dir).unwrap() {
+            check_transmission(std::fs::read_to_string(entry.unwrap().path()).unwrap().trim());
+        }
+    }
+}
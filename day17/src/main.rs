@@ -1,78 +1,534 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
 use std::ops::RangeInclusive;
 
-/// Solution for targets with X > 0 and Y < 0 only
+#[derive(Clone, Debug, PartialEq)]
+struct Target {
+    x: RangeInclusive<i32>,
+    y: RangeInclusive<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Solution {
+    highest_hit: Option<(i64, (i32, i32))>,
+    hits_count: usize,
+}
 
-const TGT_X: RangeInclusive<i32> = 119..=176;
-const TGT_Y: RangeInclusive<i32> = -141..=-84;
+/// Launch velocity that hits the target, with the step when it enters it
+/// (1st step is 1) and the highest Y of the whole trajectory
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Hit {
+    v: (i32, i32),
+    step: u32,
+    max_y: i64,
+}
 
 enum YVResult {
-    Miss(i32), Hit(i32), TooHigh(i32)
+    Miss, Hit(u32), TooHigh
 }
 
 fn main() {
-    let max_xv = *TGT_X.end();   // higher x vel will miss the target at 1st turn
-    let max_yv = -TGT_Y.start(); // positive y vels will come back to y_pos=0 with the initial velocity, but negative
-                                 // at higher velocities they will always miss the target the turn after comming back to 0
+    let input = fs::read_to_string("input.txt").expect("Can't open input.txt");
+    let target = parse_target(input.trim()).unwrap_or_else(|e| panic!("{}", e));
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        let args = parse_args(&args).unwrap_or_else(|e| panic!("{}", e));
+        if args.hits {
+            let hits = find_hits(&target).unwrap_or_else(|e| panic!("{}", e));
+            for hit in &hits {
+                println!("v=({}, {}) step={} max_y={}", hit.v.0, hit.v.1, hit.step, hit.max_y);
+            }
+        }
+        let plot_v = args.plot.or_else(|| {
+            let solution = solve(&target).ok()?;
+            solution.highest_hit.map(|(_, v)| v)
+        });
+        if let Some(v) = plot_v {
+            if args.plot.is_some() {
+                print!("{}", plot_ascii(&target, v));
+            }
+            if let Some(file) = args.svg {
+                fs::write(&file, plot_svg(&target, v))
+                    .unwrap_or_else(|e| panic!("Can't write {}: {}", file, e));
+            }
+        }
+        return;
+    }
+
+    let solution = solve(&target).unwrap_or_else(|e| panic!("{}", e));
+    let (y, (xv, yv)) = solution.highest_hit.expect("No velocity hits the target");
+    println!("Part 1: max_y={}, v=({}, {})", y, xv, yv);
+    println!("Part 2: hits count={}", solution.hits_count);
+}
+
+struct Args {
+    hits: bool,
+    plot: Option<(i32, i32)>,
+    svg: Option<String>,
+}
+
+/// Options:
+///   --hits                       list every velocity that hits the target
+///   --plot VX,VY                 print the trajectory like the puzzle diagrams
+///   --svg FILE                   write the trajectory as SVG (by default,
+///                                the highest one)
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut hits = false;
+    let mut plot = None;
+    let mut svg = None;
+
+    let mut args = args.iter();
+    while let Some(opt) = args.next() {
+        if opt == "--hits" {
+            hits = true;
+            continue;
+        }
+        let value = args.next().ok_or(format!("Missing value for option '{}'", opt))?;
+        match opt.as_str() {
+            "--plot" => {
+                let invalid = || format!("Invalid velocity '{}', expected VX,VY", value);
+                let (vx, vy) = value.split_once(',').ok_or_else(invalid)?;
+                plot = Some((vx.trim().parse().map_err(|_| invalid())?, vy.trim().parse().map_err(|_| invalid())?));
+            },
+            "--svg" => svg = Some(value.clone()),
+            _ => return Err(format!("Invalid option '{} {}'", opt, value)),
+        }
+    }
+
+    Ok(Args { hits, plot, svg })
+}
+
+/// Apply `solve_right`, that takes a target with X >= 0 and the min X
+/// velocity, to targets anywhere. Targets with X < 0 are mirrored so X > 0,
+/// and targets with X in both sides are solved as 2 separate halves. Returns
+/// the right and the (mirrored) left halves results.
+fn solve_halves<T>(
+    target: &Target,
+    solve_right: impl Fn(&Target, i32) -> Result<T, String>,
+) -> Result<(Option<T>, Option<T>), String> {
+    let (x_start, x_end) = (*target.x.start(), *target.x.end());
+    let right = Target { x: x_start.max(0)..=x_end, y: target.y.clone() };
+    let left = Target { x: (-x_end).max(0)..=-x_start, y: target.y.clone() };
+
+    let right_result = if x_end >= 0 { Some(solve_right(&right, 0)?) } else { None };
+    let left_result = if x_start < 0 {
+        // vx = 0 is already counted with the right half if it contains X = 0
+        let min_xv = if x_end >= 0 { 1 } else { 0 };
+        Some(solve_right(&left, min_xv)?)
+    } else {
+        None
+    };
+    Ok((right_result, left_result))
+}
+
+/// Solve for targets anywhere without simulating the trajectories
+fn solve(target: &Target) -> Result<Solution, String> {
+    let (right, left) = solve_halves(target, solve_right)?;
+    let mut solution = right.unwrap_or(Solution { highest_hit: None, hits_count: 0 });
+    if let Some(left_solution) = left {
+        solution.hits_count += left_solution.hits_count;
+        if let Some((max_y, (xv, yv))) = left_solution.highest_hit {
+            if solution.highest_hit.is_none_or(|(highest, _)| highest < max_y) {
+                solution.highest_hit = Some((max_y, (-xv, yv)));
+            }
+        }
+    }
+
+    Ok(solution)
+}
+
+/// Closed form solution for targets with X >= 0, with any Y. At step n,
+/// y_n = n * yv - n * (n - 1) / 2, so the y vels inside the target at step n
+/// are a range, and so are the x vels, as x_n never decreases when xv
+/// increases. The hits are the union of these ranges for all the steps.
+fn solve_right(target: &Target, min_xv: i32) -> Result<Solution, String> {
+    let (x_start, x_end) = (*target.x.start() as i64, *target.x.end() as i64);
+    let (y_start, y_end) = (*target.y.start() as i64, *target.y.end() as i64);
+    let min_xv = min_xv as i64;
+
+    // If X stops inside the target and the target contains Y = 0, any y vel
+    // hits it when it comes back to Y = 0
+    let stop_xv = partition_point(min_xv, x_end + 1, |xv| xv * (xv + 1) / 2 >= x_start);
+    if target.y.contains(&0) && stop_xv * (stop_xv + 1) / 2 <= x_end {
+        return Err("Infinite velocities hit the target".to_string());
+    }
+
+    // Same bounds as the simulation in find_hits_right()
+    let min_yv = y_start.min(0);
+    let max_yv = (-y_start - 1).max(y_end).max(if target.y.contains(&0) { x_end } else { i64::MIN });
+
+    // Ranges of x vels that hit for each y vel
+    let mut xv_ranges: Vec<Vec<(i64, i64)>> = vec![Vec::new(); (max_yv - min_yv + 1) as usize];
+    for n in 1.. {
+        let tn = n * (n - 1) / 2;
+        // From here, y_n < y_start even for max_yv
+        if n > max_yv + 1 && tn - n * max_yv > -y_start {
+            break;
+        }
+
+        let min_n_yv = (-(-(y_start + tn)).div_euclid(n)).max(min_yv);
+        let max_n_yv = (y_end + tn).div_euclid(n).min(max_yv);
+        if min_n_yv > max_n_yv {
+            continue;
+        }
+
+        let x_n = |xv: i64| if xv >= n { n * xv - tn } else { xv * (xv + 1) / 2 };
+        let min_n_xv = partition_point(min_xv, x_end + 1, |xv| x_n(xv) >= x_start);
+        let max_n_xv = partition_point(min_xv, x_end + 1, |xv| x_n(xv) > x_end) - 1;
+        if min_n_xv > max_n_xv {
+            continue;
+        }
+
+        for yv in min_n_yv..=max_n_yv {
+            xv_ranges[(yv - min_yv) as usize].push((min_n_xv, max_n_xv));
+        }
+    }
+
+    let mut highest_hit = None;
+    let mut hits_count = 0;
+    for (i, ranges) in xv_ranges.iter_mut().enumerate() {
+        if ranges.is_empty() {
+            continue;
+        }
+        ranges.sort_unstable();
+        let mut covered_until = i64::MIN;
+        for &(start, end) in ranges.iter() {
+            let start = start.max(covered_until + 1);
+            if start <= end {
+                hits_count += (end - start + 1) as usize;
+                covered_until = end;
+            }
+        }
+        let yv = min_yv + i as i64;
+        highest_hit = Some((apex(yv as i32), (ranges[0].0 as i32, yv as i32)));
+    }
+
+    Ok(Solution { highest_hit, hits_count })
+}
+
+/// First value in start..end for which pred is true, or end if none. pred must
+/// be false for some first values and true for the rest.
+fn partition_point(mut start: i64, mut end: i64, pred: impl Fn(i64) -> bool) -> i64 {
+    while start < end {
+        let mid = start + (end - start) / 2;
+        if pred(mid) {
+            end = mid;
+        } else {
+            start = mid + 1;
+        }
+    }
+    start
+}
+
+/// All the velocities that hit targets anywhere, sorted
+fn find_hits(target: &Target) -> Result<Vec<Hit>, String> {
+    let (right, left) = solve_halves(target, find_hits_right)?;
+    let mut hits = right.unwrap_or_default();
+    if let Some(left_hits) = left {
+        hits.extend(left_hits.into_iter().map(|hit| Hit { v: (-hit.v.0, hit.v.1), ..hit }));
+    }
+
+    hits.sort();
+    Ok(hits)
+}
+
+/// Find hits for targets with X >= 0, with any Y
+fn find_hits_right(target: &Target, min_xv: i32) -> Result<Vec<Hit>, String> {
+    let (y_start, y_end) = (*target.y.start(), *target.y.end());
+    let max_xv = *target.x.end();   // higher x vel will miss the target at 1st turn
 
-    let possible_x_vels = 0..=max_xv;
+    let possible_x_vels = min_xv..=max_xv;
     let valid_xv: Vec<i32> = possible_x_vels.rev()
-        .filter(|&xv| is_valid_xv(xv))
+        .filter(|&xv| is_valid_xv(target, xv))
         .collect();
-    
-    let mut highest_hit: Option<(i32, (i32, i32))> = None;
-    let mut hits_count = 0;
+
+    // If X stops inside the target and the target contains Y = 0, any y vel
+    // hits it when it comes back to Y = 0
+    if target.y.contains(&0) && valid_xv.iter().any(|&xv| target.x.contains(&(xv * (xv + 1) / 2))) {
+        return Err("Infinite velocities hit the target".to_string());
+    }
+
+    // Higher y vels will always miss the target: positive y vels will come
+    // back to y_pos=0 with the initial velocity, but negative, and will be
+    // below the target in the next turn. If the target is above, they will be
+    // above it in the 1st turn and when they come back. If the target
+    // contains Y = 0, X will have stopped out of the target when they come
+    // back to 0.
+    let min_yv = y_start.min(0);
+    let max_yv = (-y_start - 1).max(y_end).max(if target.y.contains(&0) { max_xv } else { i32::MIN });
+
+    let mut hits = Vec::new();
     for &xv in &valid_xv {
-        for yv in *TGT_Y.start()..max_yv {
-            match evaluate_yv(xv, yv) {
-                YVResult::Hit(max_y) => {
-                    hits_count += 1;
-                    if highest_hit.is_none() || highest_hit.unwrap().0 < max_y {
-                        highest_hit = Some((max_y, (xv, yv)));
-                    }
-                },
-                YVResult::Miss(_) => (),
-                YVResult::TooHigh(_) => break,
+        for yv in min_yv..=max_yv {
+            match evaluate_yv(target, xv, yv) {
+                YVResult::Hit(step) => hits.push(Hit { v: (xv, yv), step, max_y: apex(yv) }),
+                YVResult::Miss => (),
+                YVResult::TooHigh => break,
             }
         }
     }
 
-    let (y, (xv, yv)) = highest_hit.unwrap();
-    println!("Part 1: max_y={}, v=({}, {})", y, xv, yv);
-    println!("Part 2: hits count={}", hits_count);
+    Ok(hits)
+}
+
+/// Highest Y reached with the initial y vel
+fn apex(yv: i32) -> i64 {
+    let yv = yv as i64;
+    if yv > 0 { yv * (yv + 1) / 2 } else { 0 }
 }
 
-fn is_valid_xv(mut xv: i32) -> bool {
+fn is_valid_xv(target: &Target, mut xv: i32) -> bool {
     let mut x = 0;
-    while xv > 0 && x < *TGT_X.start() {
+    while xv > 0 && x < *target.x.start() {
         x += xv;
         xv -= 1;
     }
-    TGT_X.contains(&x)
+    target.x.contains(&x)
 }
 
-fn evaluate_yv(mut xv: i32, mut yv: i32) -> YVResult {
-    let (y_bottom, x_end) = (*TGT_Y.start(), *TGT_X.end());
+fn evaluate_yv(target: &Target, mut xv: i32, mut yv: i32) -> YVResult {
+    let (y_bottom, x_end) = (*target.y.start(), *target.x.end());
 
     let (mut x, mut y) = (0, 0);
-    let mut max_y = 0;
-    while y > y_bottom {
+    let mut step = 0;
+    // Higher y vels may still hit if we went under the target on the way up
+    let mut passed_under = false;
+    while y >= y_bottom || yv >= 0 {
         if xv > 0 {
             x += xv;
             xv -= 1;
         }
         y += yv;
         yv -= 1;
+        step += 1;
 
-        if y > max_y {
-            max_y = y;
+        if target.x.contains(&x) && target.y.contains(&y) {
+            return YVResult::Hit(step);
+        } else if target.x.contains(&x) && y < y_bottom {
+            passed_under = true;
+        } else if x > x_end {
+            return if passed_under { YVResult::Miss } else { YVResult::TooHigh };
         }
+    }
 
-        if TGT_X.contains(&x) && TGT_Y.contains(&y) {
-            return YVResult::Hit(max_y);
-        } else if x > x_end {
-            return YVResult::TooHigh(max_y);
+    YVResult::Miss
+}
+
+/// Positions of the probe for each step until it enters the target or falls
+/// below it, starting at the origin
+fn trajectory(target: &Target, (mut xv, mut yv): (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = (0, 0);
+    let mut points = vec![(x, y)];
+    while !(y < *target.y.start() && yv < 0) {
+        x += xv;
+        y += yv;
+        xv -= xv.signum();
+        yv -= 1;
+        points.push((x, y));
+
+        if target.x.contains(&x) && target.y.contains(&y) {
+            break;
+        }
+    }
+    points
+}
+
+/// Bounds (min_x, max_x, min_y, max_y) containing the target and the points
+fn plot_bounds(target: &Target, points: &[(i32, i32)]) -> (i32, i32, i32, i32) {
+    points.iter().fold(
+        (*target.x.start(), *target.x.end(), *target.y.start(), *target.y.end()),
+        |(x0, x1, y0, y1), &(x, y)| (x0.min(x), x1.max(x), y0.min(y), y1.max(y)))
+}
+
+/// Plot like the puzzle: S is the start, # the probe and T the target area
+fn plot_ascii(target: &Target, v: (i32, i32)) -> String {
+    let points = trajectory(target, v);
+    let (min_x, max_x, min_y, max_y) = plot_bounds(target, &points);
+    let positions: HashSet<(i32, i32)> = points.iter().copied().collect();
+
+    let mut s = String::new();
+    for y in (min_y..=max_y).rev() {
+        for x in min_x..=max_x {
+            s.push(if (x, y) == (0, 0) {
+                'S'
+            } else if positions.contains(&(x, y)) {
+                '#'
+            } else if target.x.contains(&x) && target.y.contains(&y) {
+                'T'
+            } else {
+                '.'
+            });
+        }
+        s.push('\n');
+    }
+    s
+}
+
+fn plot_svg(target: &Target, v: (i32, i32)) -> String {
+    let points = trajectory(target, v);
+    let (min_x, max_x, min_y, max_y) = plot_bounds(target, &points);
+    let (width, height) = (max_x - min_x + 2, max_y - min_y + 2);
+    // SVG Y goes down, so it's flipped with Y = max_y at the top
+    let svg_pos = |(x, y): (i32, i32)| (x - min_x + 1, max_y - y + 1);
+
+    let mut s = String::new();
+    s.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" width=\"{}\" height=\"{}\">\n",
+        width, height, width.clamp(200, 1000), height.clamp(200, 1000)));
+    let (tx, ty) = svg_pos((*target.x.start(), *target.y.end()));
+    s.push_str(&format!(
+        "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"lightgrey\" stroke=\"grey\" vector-effect=\"non-scaling-stroke\"/>\n",
+        tx as f64 - 0.5, ty as f64 - 0.5,
+        target.x.end() - target.x.start() + 1, target.y.end() - target.y.start() + 1));
+    let polyline: Vec<String> = points.iter()
+        .map(|&p| { let (x, y) = svg_pos(p); format!("{},{}", x, y) })
+        .collect();
+    s.push_str(&format!(
+        "  <polyline points=\"{}\" fill=\"none\" stroke=\"red\" vector-effect=\"non-scaling-stroke\"/>\n",
+        polyline.join(" ")));
+    s.push_str(&format!("  <title>v=({}, {})</title>\n", v.0, v.1));
+    s.push_str("</svg>\n");
+    s
+}
+
+fn parse_target(line: &str) -> Result<Target, String> {
+    let invalid = || format!("Invalid target '{}'", line);
+
+    let ranges = line.strip_prefix("target area: ").ok_or_else(invalid)?;
+    let (x, y) = ranges.split_once(", ").ok_or_else(invalid)?;
+    let parse_range = |s: &str, axis: &str| -> Result<RangeInclusive<i32>, String> {
+        let (start, end) = s.strip_prefix(axis)
+            .and_then(|s| s.split_once(".."))
+            .ok_or_else(invalid)?;
+        let start: i32 = start.parse().map_err(|_| invalid())?;
+        let end: i32 = end.parse().map_err(|_| invalid())?;
+        Ok(start.min(end)..=start.max(end))
+    };
+
+    Ok(Target { x: parse_range(x, "x=")?, y: parse_range(y, "y=")? })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve_line(line: &str) -> Result<Solution, String> {
+        solve(&parse_target(line).unwrap())
+    }
+
+    /// Brute force simulation of every velocity within some generous bounds
+    fn brute_force(target: &Target) -> Vec<Hit> {
+        let mut hits = Vec::new();
+        for xv in -100..=100i32 {
+            for yv in -100..=100 {
+                let (mut x, mut y, mut vx, mut vy) = (0, 0, xv, yv);
+                let mut max_y = 0;
+                for step in 1..300 {
+                    x += vx;
+                    y += vy;
+                    vx -= vx.signum();
+                    vy -= 1;
+                    max_y = max_y.max(y);
+                    if target.x.contains(&x) && target.y.contains(&y) {
+                        hits.push(Hit { v: (xv, yv), step, max_y: (max_y as i64).max(apex(yv)) });
+                        break;
+                    }
+                }
+            }
         }
+        hits
     }
 
-    YVResult::Miss(max_y)
+    #[test]
+    fn example() {
+        let solution = solve_line("target area: x=20..30, y=-10..-5").unwrap();
+        assert_eq!(solution.highest_hit.unwrap().0, 45);
+        assert_eq!(solution.hits_count, 112);
+    }
+
+    #[test]
+    fn other_quadrants() {
+        let solution = solve_line("target area: x=-30..-20, y=-10..-5").unwrap();
+        let (max_y, (xv, yv)) = solution.highest_hit.unwrap();
+        assert_eq!((max_y, yv), (45, 9));
+        assert!(xv < 0);
+        assert_eq!(solution.hits_count, 112);
+
+        for line in [
+            "target area: x=20..30, y=5..10",
+            "target area: x=-30..-20, y=5..10",
+            "target area: x=-5..8, y=-10..-5",
+            "target area: x=16..20, y=-3..4",
+            "target area: x=-12..-11, y=-3..4",
+        ] {
+            let target = parse_target(line).unwrap();
+            let hits = find_hits(&target).unwrap();
+            assert_eq!(hits, brute_force(&target), "{}", line);
+            let solution = solve(&target).unwrap();
+            assert_eq!(solution.hits_count, hits.len(), "{}", line);
+            assert_eq!(solution.highest_hit.map(|h| h.0), hits.iter().map(|h| h.max_y).max(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn hits_and_plot() {
+        let target = parse_target("target area: x=20..30, y=-10..-5").unwrap();
+        let hits = find_hits(&target).unwrap();
+        assert_eq!(hits, brute_force(&target));
+        assert!(hits.contains(&Hit { v: (7, 2), step: 7, max_y: 3 }));
+        assert!(hits.contains(&Hit { v: (6, 9), step: 20, max_y: 45 }));
+        assert!(!hits.iter().any(|hit| hit.v == (17, -4)));
+
+        let expected = "\
+.............#....#............
+.......#..............#........
+...............................
+S........................#.....
+...............................
+...............................
+...........................#...
+...............................
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+....................TTTTTTTT#TT
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+....................TTTTTTTTTTT
+";
+        assert_eq!(plot_ascii(&target, (7, 2)), expected);
+        let svg = plot_svg(&target, (7, 2));
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert!(svg.contains("<polyline points=\"1,4 8,2 14,1 19,1 23,2 26,4 28,7 29,11\""));
+    }
+
+    #[test]
+    fn large_target() {
+        let solution = solve_line("target area: x=2000000..2010000, y=-1000000..-990000").unwrap();
+        assert_eq!(solution.highest_hit.unwrap().0, 499_999_500_000);
+        // 10001 * 10001 hits at the 1st step, the rest in later steps
+        assert_eq!(solution.hits_count, 164_620_573);
+    }
+
+    #[test]
+    fn mid_size_targets() {
+        for (line, hits_count) in [
+            ("target area: x=200..400, y=-400..-200", 60383),
+            ("target area: x=-400..-200, y=200..400", 59717),
+        ] {
+            let target = parse_target(line).unwrap();
+            let hits = find_hits(&target).unwrap();
+            assert_eq!(hits.len(), hits_count, "{}", line);
+            let solution = solve(&target).unwrap();
+            assert_eq!(solution.hits_count, hits.len(), "{}", line);
+            assert_eq!(solution.highest_hit.map(|h| h.0), hits.iter().map(|h| h.max_y).max(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn infinite_hits() {
+        assert!(solve_line("target area: x=20..30, y=-5..5").is_err());
+        assert!(solve_line("target area: x=-2..2, y=-5..5").is_err());
+    }
 }
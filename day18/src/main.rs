@@ -1,226 +1,395 @@
+use std::cmp::Reverse;
+use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::collections::VecDeque;
 use std::fmt;
+use std::iter::Sum;
+use std::ops::Add;
+use std::str::FromStr;
+use std::thread;
+
+/// Snailfish number: a regular number or a pair of snailfish numbers. Always
+/// reduced after an addition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum SnailfishNumber {
+    Regular(u64),
+    Pair(Box<SnailfishNumber>, Box<SnailfishNumber>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Left, Right
+}
+
+/// Reduction action, with the path from the root to the pair that exploded
+/// or the regular number that was split
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Action {
+    Explode { path: Vec<Side>, pair: (u64, u64) },
+    Split { path: Vec<Side>, value: u64 },
+}
+
+/// Exploded pair, with the values that still must be added to the regular
+/// numbers at its left and right
+struct Explosion {
+    pair: (u64, u64),
+    carry: (Option<u64>, Option<u64>),
+}
 
-#[derive(Clone, PartialEq)]
-enum Token {
-    Open, Close, Num(u8)
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TraceStep {
+    action: Action,
+    before: SnailfishNumber,
+    after: SnailfishNumber,
+}
+
+/// Addition with every reduction step. Displayed like the puzzle's examples,
+/// and with the alternate flag ({:#}) each line also shows the action.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Trace {
+    addition: SnailfishNumber,
+    steps: Vec<TraceStep>,
+}
+
+/// Pair of numbers by index, with the magnitude of their sum
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct BestPair<'a> {
+    i: usize,
+    j: usize,
+    left: &'a SnailfishNumber,
+    right: &'a SnailfishNumber,
+    magnitude: u64,
+}
+
+#[derive(Clone, Debug)]
+struct ParseError {
+    what: String,
 }
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "trace") {
+        trace(&args[1..]);
+        return;
+    }
+
     let f = File::open("input.txt").expect("Error opening input.txt");
     let reader = BufReader::new(f);
     let lines = reader.lines().map(|l| l.unwrap());
 
     let mut nums = Vec::new();
     for line in lines {
-        nums.push(parse_line(&line));
+        nums.push(line.parse().unwrap_or_else(|e| panic!("{}", e)));
     }
 
     part1(&nums);
     part2(&nums);
 }
 
-fn part1(nums: &Vec<VecDeque<Token>>) {
-    let mut result = nums[0].clone();
+fn part1(nums: &[SnailfishNumber]) {
+    let result: SnailfishNumber = nums.iter().cloned().sum();
+    println!("Part 1: magnitude={}", result.magnitude());
+}
 
-    for num in &nums[1..] {
-        result = sum(&result, num);
-    }
+fn part2(nums: &[SnailfishNumber]) {
+    let best = largest_pair(nums).expect("At least 2 numbers are needed");
+    println!("Part 2: max magnitude={}, #{} + #{}", best.magnitude, best.i + 1, best.j + 1);
+    println!("  {}\n+ {}", best.left, best.right);
+}
 
-    result.make_contiguous();
-    let mag = magnitude(result.as_slices().0);
+/// Search the pair of different numbers whose sum has the largest magnitude,
+/// splitting the left operands between threads. Ties are solved with the
+/// lowest indexes, so the result doesn't depend on the threads.
+fn largest_pair(nums: &[SnailfishNumber]) -> Option<BestPair<'_>> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(nums.len().max(1));
+
+    // Interleaved rows, so all the threads get short and long numbers
+    let search_rows = |first: usize| {
+        let mut best: Option<(u64, usize, usize)> = None;
+        for i in (first..nums.len()).step_by(threads) {
+            for j in (0..nums.len()).filter(|&j| j != i) {
+                let magnitude = (nums[i].clone() + nums[j].clone()).magnitude();
+                if best.is_none_or(|(max, _, _)| magnitude > max) {
+                    best = Some((magnitude, i, j));
+                }
+            }
+        }
+        best
+    };
 
-    println!("Part 1: magnitude={}", mag);
+    let results: Vec<(u64, usize, usize)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|first| scope.spawn(move || search_rows(first)))
+            .collect();
+        handles.into_iter()
+            .filter_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    results.into_iter()
+        .min_by_key(|&(magnitude, i, j)| (Reverse(magnitude), i, j))
+        .map(|(magnitude, i, j)| BestPair { i, j, left: &nums[i], right: &nums[j], magnitude })
 }
 
-fn part2(nums: &Vec<VecDeque<Token>>) {
-    let mut max = 0;
+/// Trace the additions of the numbers in the command line, from left to
+/// right: trace [--actions] NUM NUM...
+fn trace(args: &[String]) {
+    let (actions, args) = match args.first() {
+        Some(arg) if arg == "--actions" => (true, &args[1..]),
+        _ => (false, args),
+    };
+    let mut nums = args.iter().map(|arg| arg.parse::<SnailfishNumber>().unwrap_or_else(|e| panic!("{}", e)));
+    let mut result = nums.next().expect("Usage: trace [--actions] NUM NUM...");
 
-    for i in 0..nums.len() {
-        for j in 0..nums.len() {
-            let mut result = sum(&nums[i], &nums[j]);
-            result.make_contiguous();
-            let mag = magnitude(result.as_slices().0);
-            if mag > max {
-                max = mag;
-            }
+    for num in nums {
+        let trace = result.add_traced(num);
+        if actions {
+            print!("{:#}", trace);
+        } else {
+            print!("{}", trace);
         }
+        println!();
+        result = trace.result().clone();
     }
-
-    println!("Part 2: max magnitude={}", max);
+    println!("result: {}", result);
 }
 
-fn sum(tokens1: &VecDeque<Token>, tokens2: &VecDeque<Token>) -> VecDeque<Token> {
-    let mut result = VecDeque::with_capacity(2 + tokens1.len() + tokens2.len());
-    result.push_front(Token::Open);
-    result.extend(tokens1.clone());
-    result.extend(tokens2.clone());
-    result.push_back(Token::Close);
+impl SnailfishNumber {
+    fn pair(left: SnailfishNumber, right: SnailfishNumber) -> Self {
+        SnailfishNumber::Pair(Box::new(left), Box::new(right))
+    }
+
+    fn magnitude(&self) -> u64 {
+        match self {
+            SnailfishNumber::Regular(n) => *n,
+            SnailfishNumber::Pair(left, right) => 3 * left.magnitude() + 2 * right.magnitude(),
+        }
+    }
 
-    loop {
-        let explodes = reduce_all_explodes(&mut result);
-        let splits = reduce_one_split(&mut result);
-        if !explodes && !splits {
-            break;
+    /// Add without reducing, to trace the reduction
+    fn add_traced(self, other: SnailfishNumber) -> Trace {
+        let addition = SnailfishNumber::pair(self, other);
+        let mut steps = Vec::new();
+        let mut num = addition.clone();
+        loop {
+            let before = num.clone();
+            match num.reduce_step() {
+                Some(action) => steps.push(TraceStep { action, before, after: num.clone() }),
+                None => break,
+            }
         }
+        Trace { addition, steps }
     }
 
-    result
-}
+    fn reduce(&mut self) {
+        while self.reduce_step().is_some() {}
+    }
 
-fn reduce_all_explodes(tokens: &mut VecDeque<Token>) -> bool {
-    let mut has_explode = false;
-    loop {
-        let mut lvl = 0;
-        let mut new_explode = false;
-        for i in 0..tokens.len() {
-            match tokens[i] {
-                Token::Open => lvl += 1,
-                Token::Close if lvl > 4 => {
-                    explode(tokens, i);
-                    new_explode = true;
-                    break;
-                },
-                Token::Close => lvl -= 1,
-                _ => (),
+    /// Do the 1st reduction action, if any is needed
+    fn reduce_step(&mut self) -> Option<Action> {
+        let mut path = Vec::new();
+        if let Some(explosion) = self.explode(&mut path) {
+            return Some(Action::Explode { path, pair: explosion.pair });
+        }
+        self.split(&mut path).map(|value| Action::Split { path, value })
+    }
+
+    /// Explode the leftmost pair nested inside 4 pairs, leaving its path in
+    /// path
+    fn explode(&mut self, path: &mut Vec<Side>) -> Option<Explosion> {
+        let SnailfishNumber::Pair(left, right) = self else {
+            return None;
+        };
+
+        if path.len() >= 4 {
+            if let (SnailfishNumber::Regular(l), SnailfishNumber::Regular(r)) = (&**left, &**right) {
+                let pair = (*l, *r);
+                *self = SnailfishNumber::Regular(0);
+                return Some(Explosion { pair, carry: (Some(pair.0), Some(pair.1)) });
             }
         }
-        if new_explode {
-            has_explode = true;
-        } else {
-            break;
+
+        path.push(Side::Left);
+        if let Some(mut explosion) = left.explode(path) {
+            if let Some(n) = explosion.carry.1.take() {
+                right.add_leftmost(n);
+            }
+            return Some(explosion);
         }
+        *path.last_mut().unwrap() = Side::Right;
+        if let Some(mut explosion) = right.explode(path) {
+            if let Some(n) = explosion.carry.0.take() {
+                left.add_rightmost(n);
+            }
+            return Some(explosion);
+        }
+        path.pop();
+        None
     }
-    has_explode
-}
 
-fn explode(tokens: &mut VecDeque<Token>, end: usize) {
-    let src_left = match tokens[end - 2] {
-        Token::Num(n) => n,
-        _ => panic!(),
-    };
-    let src_right = match tokens[end - 1] {
-        Token::Num(n) => n,
-        _ => panic!(),
-    };
+    fn add_leftmost(&mut self, n: u64) {
+        match self {
+            SnailfishNumber::Regular(m) => *m += n,
+            SnailfishNumber::Pair(left, _) => left.add_leftmost(n),
+        }
+    }
 
-    if let Some((dst_left_pos, dst_left)) = find_prev_num(tokens, end - 3) {
-        tokens[dst_left_pos] = Token::Num(src_left + dst_left);
+    fn add_rightmost(&mut self, n: u64) {
+        match self {
+            SnailfishNumber::Regular(m) => *m += n,
+            SnailfishNumber::Pair(_, right) => right.add_rightmost(n),
+        }
     }
-    if let Some((dst_right_pos, dst_right)) = find_next_num(tokens, end) {
-        tokens[dst_right_pos] = Token::Num(src_right + dst_right);
+
+    /// Split the leftmost regular number that is 10 or greater, leaving its
+    /// path in path. Returns the number that was split.
+    fn split(&mut self, path: &mut Vec<Side>) -> Option<u64> {
+        match self {
+            SnailfishNumber::Regular(n) if *n >= 10 => {
+                let n = *n;
+                *self = SnailfishNumber::pair(
+                    SnailfishNumber::Regular(n / 2), SnailfishNumber::Regular(n - n / 2));
+                Some(n)
+            },
+            SnailfishNumber::Regular(_) => None,
+            SnailfishNumber::Pair(left, right) => {
+                path.push(Side::Left);
+                if let Some(n) = left.split(path) {
+                    return Some(n);
+                }
+                *path.last_mut().unwrap() = Side::Right;
+                if let Some(n) = right.split(path) {
+                    return Some(n);
+                }
+                path.pop();
+                None
+            },
+        }
     }
+}
 
-    tokens.remove(end);
-    tokens.remove(end - 1);
-    tokens.remove(end - 2);
-    tokens[end - 3] = Token::Num(0);
+impl Trace {
+    fn result(&self) -> &SnailfishNumber {
+        self.steps.last().map_or(&self.addition, |step| &step.after)
+    }
 }
 
-fn reduce_one_split(tokens: &mut VecDeque<Token>) -> bool {
-    // assumed no explodes must be done
-    for i in 0..tokens.len() {
-        match tokens[i] {
-            Token::Num(n) if n > 9 => {
-                tokens[i] = Token::Open;
-                tokens.insert(i + 1, Token::Num(n / 2));
-                tokens.insert(i + 2, Token::Num(n / 2 + n % 2));
-                tokens.insert(i + 3, Token::Close);
-                return true;
-            },
-            _ => (),
+impl Add for SnailfishNumber {
+    type Output = SnailfishNumber;
+
+    fn add(self, other: SnailfishNumber) -> SnailfishNumber {
+        let mut result = SnailfishNumber::pair(self, other);
+        result.reduce();
+        result
+    }
+}
+
+/// Panics if there aren't any numbers, as there is no zero snailfish number
+impl Sum for SnailfishNumber {
+    fn sum<I: Iterator<Item = SnailfishNumber>>(mut iter: I) -> Self {
+        let first = iter.next().expect("Can't sum 0 snailfish numbers");
+        iter.fold(first, |acc, num| acc + num)
+    }
+}
+
+impl FromStr for SnailfishNumber {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pos = 0;
+        let num = parse_number(s.as_bytes(), &mut pos)?;
+        if pos != s.len() {
+            let ch = s[pos..].chars().next().unwrap();
+            return Err(ParseError::new(format!("Unexpected '{}' at {} in '{}'", ch, pos, s)));
         }
+        Ok(num)
     }
-    false
 }
 
-fn find_prev_num(tokens: &mut VecDeque<Token>, pos: usize) -> Option<(usize, u8)> {
-    for pos in (1..pos).rev() {
-        if let Token::Num(n) = tokens[pos] {
-            return Some((pos, n));
+fn parse_number(s: &[u8], pos: &mut usize) -> Result<SnailfishNumber, ParseError> {
+    let expect = |pos: &mut usize, ch: u8| {
+        if s.get(*pos) == Some(&ch) {
+            *pos += 1;
+            Ok(())
+        } else {
+            Err(ParseError::new(format!("Expected '{}' at {}", ch as char, *pos)))
         }
+    };
+
+    if s.get(*pos) == Some(&b'[') {
+        *pos += 1;
+        let left = parse_number(s, pos)?;
+        expect(pos, b',')?;
+        let right = parse_number(s, pos)?;
+        expect(pos, b']')?;
+        return Ok(SnailfishNumber::pair(left, right));
+    }
+
+    let start = *pos;
+    while s.get(*pos).is_some_and(|ch| ch.is_ascii_digit()) {
+        *pos += 1;
     }
-    None
+    // Only ASCII digits, so it's valid UTF-8
+    std::str::from_utf8(&s[start..*pos]).unwrap().parse()
+        .map(SnailfishNumber::Regular)
+        .map_err(|_| ParseError::new(format!("Expected a number or '[' at {}", start)))
 }
 
-fn find_next_num(tokens: &mut VecDeque<Token>, pos: usize) -> Option<(usize, u8)> {
-    for pos in pos + 1..tokens.len() - 1 {
-        if let Token::Num(n) = tokens[pos] {
-            return Some((pos, n));
+impl fmt::Display for SnailfishNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnailfishNumber::Regular(n) => write!(f, "{}", n),
+            SnailfishNumber::Pair(left, right) => write!(f, "[{},{}]", left, right),
         }
     }
-    None
 }
 
-fn magnitude(tokens: &[Token]) -> u32 {
-    let mut pos = 1;
-    let v1 = next_pair_val(tokens, &mut pos);
-    let v2 = next_pair_val(tokens, &mut pos);
-    3 * v1 + 2 * v2
+fn path_to_string(path: &[Side]) -> String {
+    if path.is_empty() {
+        return "root".to_string();
+    }
+    let sides: Vec<&str> = path.iter()
+        .map(|side| if *side == Side::Left { "L" } else { "R" })
+        .collect();
+    sides.join("-")
 }
 
-fn next_pair_val(tokens: &[Token], pos: &mut usize) -> u32 {
-    if let Token::Num(n) = tokens[*pos] {
-        *pos += 1;
-        n as u32
-    } else {
-        let start = *pos;
-        *pos += find_pair_close(&tokens[start..]) + 1;
-        magnitude(&tokens[start..*pos])
-    }
-}
-
-fn find_pair_close(tokens: &[Token]) -> usize {
-    let mut lvl = 0;
-    for i in 0..tokens.len() {
-        match tokens[i] {
-            Token::Open => lvl += 1,
-            Token::Close if lvl > 1 => lvl -= 1,
-            Token::Close => return i,
-            _ => (),
-        }
-    }
-    0
-}
-
-fn parse_line(line: &str) -> VecDeque<Token> {
-    let mut tokens = VecDeque::new();
-    let mut num_buf = String::new();
-    for ch in line.chars() {
-        match ch {
-            '[' => tokens.push_back(Token::Open),
-            ch @ '0'..='9' => num_buf.push(ch),
-            ch @ (']' | ',') => {
-                if num_buf.len() > 0 {
-                    let num = num_buf.parse().unwrap();
-                    tokens.push_back(Token::Num(num));
-                    num_buf.clear();
-                }
-                if ch == ']' {
-                    tokens.push_back(Token::Close);
-                }
-            },
-            _ => panic!(),
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Explode { path, pair } => write!(f, "explode [{},{}] at {}", pair.0, pair.1, path_to_string(path)),
+            Action::Split { path, value } => write!(f, "split of {} at {}", value, path_to_string(path)),
         }
     }
-    tokens
 }
 
-impl fmt::Display for Token {
+impl fmt::Display for Trace {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Token::Open => write!(f, "["),
-            Token::Close =>  write!(f, "]"),
-            Token::Num(n) => write!(f, "{} ", n),
+        writeln!(f, "after addition: {}", self.addition)?;
+        for step in &self.steps {
+            let label = match step.action {
+                Action::Explode { .. } => "after explode: ",
+                Action::Split { .. } => "after split:   ",
+            };
+            if f.alternate() {
+                writeln!(f, "{} {}  ({})", label, step.after, step.action)?;
+            } else {
+                writeln!(f, "{} {}", label, step.after)?;
+            }
         }
+        Ok(())
     }
 }
 
-impl fmt::Debug for Token {
+impl ParseError {
+    fn new(what: String) -> Self {
+        ParseError { what }
+    }
+}
+
+impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        <Self as fmt::Display>::fmt(self, f)
+        write!(f, "{}", self.what)
     }
 }
 
@@ -229,26 +398,96 @@ impl fmt::Debug for Token {
 mod tests {
     use super::*;
 
+    const EXAMPLE: [&str; 10] = [
+        "[[[0,[5,8]],[[1,7],[9,6]]],[[4,[1,2]],[[1,4],2]]]",
+        "[[[5,[2,8]],4],[5,[[9,9],0]]]",
+        "[6,[[[6,2],[5,6]],[[7,6],[4,7]]]]",
+        "[[[6,[0,7]],[0,9]],[4,[9,[9,0]]]]",
+        "[[[7,[6,4]],[3,[1,3]]],[[[5,5],1],9]]",
+        "[[6,[[7,3],[3,2]]],[[[3,8],[5,7]],4]]",
+        "[[[[5,4],[7,7]],8],[[8,3],8]]",
+        "[[9,3],[[9,9],[6,[4,9]]]]",
+        "[[2,[[7,7],7]],[[5,8],[[9,3],[0,2]]]]",
+        "[[[[5,2],5],[8,[3,7]]],[[5,[7,5]],[4,4]]]",
+    ];
+
+    fn num(s: &str) -> SnailfishNumber {
+        s.parse().unwrap()
+    }
+
     #[test]
     fn parse() {
-        let tokens = parse_line("[[1,2],[[3,4],5]]");
-
-        let expected = VecDeque::from(vec![
-            Token::Open,
-                Token::Open, Token::Num(1), Token::Num(2), Token::Close,
-                Token::Open,
-                    Token::Open, Token::Num(3), Token::Num(4), Token::Close,
-                    Token::Num(5),
-                Token::Close,
-            Token::Close
-        ]);
-        assert_eq!(tokens, expected);
+        let regular = SnailfishNumber::Regular;
+        let pair = SnailfishNumber::pair;
+        let expected = pair(
+            pair(regular(1), regular(2)),
+            pair(pair(regular(3), regular(4)), regular(5)));
+        assert_eq!(num("[[1,2],[[3,4],5]]"), expected);
+
+        for s in ["[[1,2],[[3,4],5]]", "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]", "[15,[0,13]]", "7"] {
+            assert_eq!(num(s).to_string(), s);
+        }
+        for s in ["", "[1,2", "[1;2]", "[1,2]]", "[,2]", "[1,2] ", "[1,2]é", "99999999999999999999999"] {
+            assert!(s.parse::<SnailfishNumber>().is_err(), "{}", s);
+        }
     }
 
     #[test]
     fn magnitude_test() {
-        let mut tokens = parse_line("[[1,2],[[3,4],5]]");
-        tokens.make_contiguous();
-        assert_eq!(magnitude(tokens.as_slices().0), 143);
+        assert_eq!(num("[[1,2],[[3,4],5]]").magnitude(), 143);
+        assert_eq!(num("[[[[8,7],[7,7]],[[8,6],[7,7]]],[[[0,7],[6,6]],[8,7]]]").magnitude(), 3488);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn trace() {
+        let trace = num("[[[[4,3],4],4],[7,[[8,4],9]]]").add_traced(num("[1,1]"));
+        let expected = "\
+after addition: [[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]
+after explode:  [[[[0,7],4],[7,[[8,4],9]]],[1,1]]
+after explode:  [[[[0,7],4],[15,[0,13]]],[1,1]]
+after split:    [[[[0,7],4],[[7,8],[0,13]]],[1,1]]
+after split:    [[[[0,7],4],[[7,8],[0,[6,7]]]],[1,1]]
+after explode:  [[[[0,7],4],[[7,8],[6,0]]],[8,1]]
+";
+        assert_eq!(trace.to_string(), expected);
+        assert_eq!(trace.result(), &num("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]"));
+
+        let actions: Vec<String> = trace.steps.iter().map(|step| step.action.to_string()).collect();
+        assert_eq!(actions, [
+            "explode [4,3] at L-L-L-L",
+            "explode [8,4] at L-R-R-L",
+            "split of 15 at L-R-L",
+            "split of 13 at L-R-R-R",
+            "explode [6,7] at L-R-R-R",
+        ]);
+        for pair in trace.steps.windows(2) {
+            assert_eq!(pair[0].after, pair[1].before);
+        }
+        assert_eq!(trace.steps[0].before, trace.addition);
+    }
+
+    #[test]
+    fn largest_pair_test() {
+        let nums: Vec<SnailfishNumber> = EXAMPLE.iter().map(|s| num(s)).collect();
+        let best = largest_pair(&nums).unwrap();
+        assert_eq!((best.i, best.j, best.magnitude), (8, 0, 3993));
+        assert_eq!(best.left, &nums[8]);
+        assert_eq!(best.right, &nums[0]);
+
+        // [9,9] + [9,9] would be the largest
+        let nums = [num("[9,9]"), num("[1,1]")];
+        let best = largest_pair(&nums).unwrap();
+        assert_eq!((best.i, best.j), (0, 1));
+        assert!(largest_pair(&nums[..1]).is_none());
+        assert!(largest_pair(&[]).is_none());
+    }
+
+    #[test]
+    fn add_and_sum() {
+        assert_eq!(num("[[[[4,3],4],4],[7,[[8,4],9]]]") + num("[1,1]"), num("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]"));
+
+        let result: SnailfishNumber = EXAMPLE.iter().map(|s| num(s)).sum();
+        assert_eq!(result.to_string(), "[[[[6,6],[7,6]],[[7,7],[7,0]]],[[[7,7],[7,7]],[[7,8],[9,9]]]]");
+        assert_eq!(result.magnitude(), 4140);
+    }
+}
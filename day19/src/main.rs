@@ -1,51 +1,104 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::collections::BTreeSet;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fmt;
 
 type Coords = [i32; 3];
 
+/// 3x3 integer matrix by rows, to transform column vectors
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Matrix([[i32; 3]; 3]);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct AlignOptions {
+    /// Min count of common beacons to align 2 scanners
+    min_overlap: usize,
+    /// Max difference in each coordinate between 2 readings of the same beacon
+    tolerance: i32,
+}
+
+struct Scanner {
+    beacons: Vec<Coords>,
+    /// Distance fingerprint of each pair of beacons
+    fingerprints: Vec<(Coords, (usize, usize))>,
+}
+
+/// Coords grouped in cubic cells of side tolerance + 1, so the coords within
+/// the tolerance of some others are in the same or in adjacent cells
+struct Grid<T> {
+    tolerance: i32,
+    cells: HashMap<Coords, Vec<(Coords, T)>>,
+}
+
+/// Scanner aligned to scanner 0 coords
+#[derive(Clone, Debug)]
+struct Placement {
+    scanner: usize,
+    position: Coords,
+    rotation: Matrix,
+    beacons: Vec<Coords>,
+}
+
+/// Beacon of the map, with the ids of the scanners that saw it
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct MapBeacon {
+    coords: Coords,
+    scanners: Vec<usize>,
+}
+
+struct Args {
+    options: AlignOptions,
+    csv: Option<String>,
+    ply: Option<String>,
+}
+
+/// Some scanners can't be aligned to scanner 0, directly or through others
+#[derive(Clone, Debug)]
+struct AlignError {
+    orphans: Vec<usize>,
+    partial: Vec<Option<Placement>>,
+}
+
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Args { options, csv, ply } = parse_args(&args).unwrap_or_else(|e| panic!("{}", e));
+
     let f = File::open("input.txt").expect("Error opening input.txt");
     let reader = BufReader::new(f);
     let mut lines = reader.lines().map(|l| l.unwrap());
 
-    let mut scanners_all_rotations = Vec::new();
+    let mut scanners = Vec::new();
     while let Some(beacons) = parse_scanner_beacons(&mut lines) {
-        let beacons_all_rotations = calculate_all_rotations(beacons);
-        scanners_all_rotations.push(beacons_all_rotations);
+        scanners.push(Scanner::new(beacons));
     }
 
-    // scanner 0 coords (without transformation) used as reference
-    let mut beacons_abs = vec![scanners_all_rotations.swap_remove(0).swap_remove(0)];
-    let mut scanners_abs = vec![[0, 0, 0]];
-
-    while scanners_all_rotations.len() > 0 {
-        for i in (0..scanners_all_rotations.len()).rev() {
-            let scanner = &scanners_all_rotations[i];
-
-            for beacons in &beacons_abs {
-                if let Some((scanner_coords,new_beacons)) = get_overlapping_beacons(scanner, beacons) {
-                    beacons_abs.push(new_beacons);
-                    scanners_abs.push(scanner_coords);
-                    scanners_all_rotations.remove(i);
-                    println!("{} scanners left", scanners_all_rotations.len());
-                    break;
-                }
-            }
-        }
-    }
+    let placements: Vec<Placement> = match align(&scanners, &options) {
+        Ok(placements) => placements,
+        Err(e) => {
+            eprintln!("{}, using a partial map", e);
+            e.partial.into_iter().flatten().collect()
+        },
+    };
 
-    // remove duplicates
-    let beacons_abs = beacons_abs.iter()
-        .flatten()
-        .copied()
-        .collect::<BTreeSet<Coords>>();
+    let beacons_abs = map_beacons(&placements, options.tolerance);
     println!("Part 1: beacons count={}", beacons_abs.len());
 
+    if let Some(filename) = csv {
+        File::create(&filename)
+            .and_then(|f| write_csv(BufWriter::new(f), &placements, &beacons_abs))
+            .unwrap_or_else(|e| panic!("Can't write {}: {}", filename, e));
+    }
+    if let Some(filename) = ply {
+        File::create(&filename)
+            .and_then(|f| write_ply(BufWriter::new(f), &placements, &beacons_abs))
+            .unwrap_or_else(|e| panic!("Can't write {}: {}", filename, e));
+    }
+
     let mut max = 0;
-    while let Some(coords1) = scanners_abs.pop() {
-        for coords2 in &scanners_abs {
-            let diff = sub_coords(*coords2, coords1);
+    for (i, p1) in placements.iter().enumerate() {
+        for p2 in &placements[i + 1..] {
+            let diff = sub_coords(p2.position, p1.position);
             let manhattan_dist = i32::abs(diff[0]) + i32::abs(diff[1]) + i32::abs(diff[2]);
             if manhattan_dist > max {
                 max = manhattan_dist;
@@ -55,93 +108,366 @@ fn main() {
     println!("Part 2: max mahattan dist={}", max);
 }
 
-fn calculate_all_rotations(beacons: Vec<Coords>) -> Vec<Vec<Coords>> {
-    let orientations = [ // conversions for X axis facing other directions
-        // axis        sign
-        ([0, 1, 2], [1, 1, 1]),   // X
-        ([2, 1, 0], [1, 1, -1]),  // -Z
-        ([0, 1, 2], [-1, 1, -1]), // -X
-        ([2, 1, 0], [-1, 1, 1]),  // Z
-        ([1, 0, 2], [-1, 1, 1]),  // Y
-        ([1, 0, 2], [1, -1, 1])   // -Y
-    ];
-    let rotations = [ // conversions for rotations around X axis
-        ([0, 1, 2], [1, 1, 1]),   // 0
-        ([0, 2, 1], [1, -1, 1]),  // 90
-        ([0, 1, 2], [1, -1, -1]), // 180
-        ([0, 2, 1], [1, 1, -1])   // 270
-    ];
+/// Options:
+///   --min-overlap N              common beacons to align 2 scanners (12)
+///   --tolerance N                max difference in each coordinate between 2
+///                                readings of the same beacon (0)
+///   --csv FILE                   export scanners and beacons as CSV
+///   --ply FILE                   export scanners and beacons as a PLY point
+///                                cloud, scanners in red
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut options = AlignOptions::default();
+    let mut csv = None;
+    let mut ply = None;
 
-    let mut beacons_all_rotations = Vec::new();
-    for orientation in &orientations {
-        for rotation in &rotations {
-            let beacons_this_axes = beacons.iter()
-                .map(|coords| axes_convert(*rotation, axes_convert(*orientation, *coords)))
-                .collect();
-            beacons_all_rotations.push(beacons_this_axes);
+    let mut args = args.iter();
+    while let Some(opt) = args.next() {
+        let value = args.next().ok_or(format!("Missing value for option '{}'", opt))?;
+        let invalid = || format!("Invalid option '{} {}'", opt, value);
+        match opt.as_str() {
+            "--min-overlap" => {
+                options.min_overlap = value.parse().map_err(|_| invalid())?;
+                if options.min_overlap < 2 {
+                    return Err(invalid());
+                }
+            },
+            "--tolerance" => {
+                options.tolerance = value.parse().map_err(|_| invalid())?;
+                if options.tolerance < 0 {
+                    return Err(invalid());
+                }
+            },
+            "--csv" => csv = Some(value.clone()),
+            "--ply" => ply = Some(value.clone()),
+            _ => return Err(invalid()),
         }
     }
-    beacons_all_rotations
+
+    Ok(Args { options, csv, ply })
 }
 
-fn axes_convert(conversion: ([usize;3], [i32;3]), coords: Coords) -> Coords {
-    let (axes, mult) = conversion;
-    [coords[axes[0]] * mult[0], coords[axes[1]] * mult[1], coords[axes[2]] * mult[2]]
+impl Default for AlignOptions {
+    fn default() -> Self {
+        AlignOptions { min_overlap: 12, tolerance: 0 }
+    }
 }
 
-fn get_overlapping_beacons(scanner: &Vec<Vec<Coords>>, beacons: &Vec<Coords>) -> Option<(Coords, Vec<Coords>)> {
-    for candidate_beacons in scanner {
-        if let Some(offset) = calc_offset(beacons, candidate_beacons) {
-            let transformed_beacons = candidate_beacons.iter()
-                .map(|coords| sub_coords(*coords, offset))
-                .collect();
-            let scanner_coords = sub_coords([0, 0, 0], offset);
-            return Some((scanner_coords, transformed_beacons));
+impl Scanner {
+    fn new(beacons: Vec<Coords>) -> Self {
+        let mut fingerprints = Vec::new();
+        for i in 0..beacons.len() {
+            for j in i + 1..beacons.len() {
+                fingerprints.push((fingerprint(beacons[i], beacons[j]), (i, j)));
+            }
         }
+        Scanner { beacons, fingerprints }
     }
-    None
 }
 
-fn calc_offset(beacons1: &Vec<Coords>, beacons2: &Vec<Coords>) -> Option<Coords> {
-    for &b1 in beacons1 {
-        for &b2 in beacons2 {
-            let offset = sub_coords(b2, b1);
-            if try_offset(beacons1, beacons2, offset) {
-                return Some(offset);
+/// Distance between 2 beacons that doesn't depend on the rotation: sorted
+/// absolute differences in each axis
+fn fingerprint(c1: Coords, c2: Coords) -> Coords {
+    let mut diff = sub_coords(c1, c2).map(i32::abs);
+    diff.sort_unstable();
+    diff
+}
+
+impl<T> Grid<T> {
+    fn new(tolerance: i32) -> Self {
+        Grid { tolerance, cells: HashMap::new() }
+    }
+
+    fn cell(&self, coords: Coords) -> Coords {
+        coords.map(|c| c.div_euclid(self.tolerance + 1))
+    }
+
+    fn insert(&mut self, coords: Coords, value: T) {
+        self.cells.entry(self.cell(coords)).or_default().push((coords, value));
+    }
+
+    /// Entries with coords within the tolerance in each coordinate
+    fn near(&self, coords: Coords) -> impl Iterator<Item = &(Coords, T)> + '_ {
+        let cell = self.cell(coords);
+        let r = if self.tolerance == 0 { 0 } else { 1 };
+        let tolerance = self.tolerance;
+        (-r..=r)
+            .flat_map(move |dx| (-r..=r).flat_map(move |dy| (-r..=r).map(move |dz| [dx, dy, dz])))
+            .filter_map(move |d| self.cells.get(&add_coords(cell, d)))
+            .flatten()
+            .filter(move |(other, _)| sub_coords(*other, coords).iter().all(|d| d.abs() <= tolerance))
+    }
+}
+
+/// Align the scanners to scanner 0 coords. Scanner pairs are only tried if
+/// they share enough fingerprints for min_overlap beacons, and then only the
+/// beacons pairs with the same fingerprint are tried as anchors. Fails with
+/// the partial map if some scanners can't be aligned.
+fn align(scanners: &[Scanner], options: &AlignOptions) -> Result<Vec<Placement>, AlignError> {
+    let mut placements: Vec<Option<Placement>> = vec![None; scanners.len()];
+    if scanners.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let rotations = calculate_all_rotations();
+    let min_common = options.min_overlap * (options.min_overlap - 1) / 2;
+    // A fingerprint adds the differences of 2 beacons readings
+    let fingerprints: Vec<Grid<(usize, usize)>> = scanners.iter()
+        .map(|scanner| {
+            let mut grid = Grid::new(2 * options.tolerance);
+            for &(key, pair) in &scanner.fingerprints {
+                grid.insert(key, pair);
+            }
+            grid
+        })
+        .collect();
+
+    // scanner 0 coords (without transformation) used as reference
+    placements[0] = Some(Placement {
+        scanner: 0,
+        position: [0, 0, 0],
+        rotation: Matrix::IDENTITY,
+        beacons: scanners[0].beacons.clone(),
+    });
+    let mut queue = VecDeque::from([0]);
+
+    while let Some(i) = queue.pop_front() {
+        let placed = placements[i].clone().unwrap();
+        let mut placed_grid = Grid::new(2 * options.tolerance);
+        for &coords in &placed.beacons {
+            placed_grid.insert(coords, ());
+        }
+
+        for j in 0..scanners.len() {
+            if placements[j].is_some() {
+                continue;
+            }
+            // Pairs of the new scanner with some similar pair in the placed one
+            let common = scanners[j].fingerprints.iter()
+                .filter(|(key, _)| fingerprints[i].near(*key).next().is_some())
+                .count();
+            if common < min_common {
+                continue;
+            }
+            if let Some(placement) = place_scanner(&placed, &placed_grid, &fingerprints[i], j, &scanners[j], &rotations, options) {
+                placements[j] = Some(placement);
+                queue.push_back(j);
+            }
+        }
+    }
+
+    let orphans: Vec<usize> = (0..scanners.len()).filter(|&i| placements[i].is_none()).collect();
+    if !orphans.is_empty() {
+        return Err(AlignError { orphans, partial: placements });
+    }
+    Ok(placements.into_iter().flatten().collect())
+}
+
+/// Find the rotation and offset that make the scanner match min_overlap
+/// beacons of the placed one. For each pair of beacons with a similar
+/// fingerprint in both scanners, the rotation must convert the vector between
+/// them in the new scanner into the vector in the placed one.
+fn place_scanner(placed: &Placement, placed_grid: &Grid<()>, placed_fingerprints: &Grid<(usize, usize)>,
+                 id: usize, scanner: &Scanner, rotations: &[Matrix], options: &AlignOptions) -> Option<Placement> {
+    let tolerance = options.tolerance;
+    let near = |c1: Coords, c2: Coords| sub_coords(c1, c2).iter().all(|d| d.abs() <= 2 * tolerance);
+
+    for &(key, (a2, b2)) in &scanner.fingerprints {
+        let (a2, b2) = (scanner.beacons[a2], scanner.beacons[b2]);
+        for &(_, (a1, b1)) in placed_fingerprints.near(key) {
+            let (a1, b1) = (placed.beacons[a1], placed.beacons[b1]);
+            let vector1 = sub_coords(b1, a1);
+
+            for &rotation in rotations {
+                // a1 may match a2 or b2
+                for (anchor, other) in [(a2, b2), (b2, a2)] {
+                    let (anchor, other) = (rotation.apply(anchor), rotation.apply(other));
+                    if !near(sub_coords(other, anchor), vector1) {
+                        continue;
+                    }
+                    let rotated: Vec<Coords> = scanner.beacons.iter()
+                        .map(|&coords| rotation.apply(coords))
+                        .collect();
+                    // With noise, the anchor may be off by the tolerance, so
+                    // the readings of the same beacon by the placed scanner
+                    // may be off by 2 times the tolerance
+                    let mut position = sub_coords(a1, anchor);
+                    let matched = |position: Coords| -> Vec<(Coords, Coords)> {
+                        rotated.iter()
+                            .filter_map(|&coords| {
+                                let coords = add_coords(coords, position);
+                                placed_grid.near(coords).next().map(|&(other, _)| (coords, other))
+                            })
+                            .collect()
+                    };
+                    let matches = matched(position);
+                    if matches.len() < options.min_overlap {
+                        continue;
+                    }
+                    if tolerance > 0 {
+                        position = refine_position(position, &matches);
+                        if matched(position).len() < options.min_overlap {
+                            continue;
+                        }
+                    }
+                    let beacons = rotated.iter().map(|&coords| add_coords(coords, position)).collect();
+                    return Some(Placement { scanner: id, position, rotation, beacons });
+                }
             }
         }
     }
     None
 }
 
-fn try_offset(beacons1: &Vec<Coords>, beacons2: &Vec<Coords>, offset: Coords) -> bool {
-    let mut matches = 0;
-    for &b2 in beacons2 {
-        let b2 = sub_coords(b2, offset);
-        for &b1 in beacons1 {
-            if b1 == b2 {
-                matches += 1;
-                break;
+/// Move the position by the mean difference of the matched beacons
+fn refine_position(position: Coords, matches: &[(Coords, Coords)]) -> Coords {
+    let mut sum = [0i64; 3];
+    for (coords, other) in matches {
+        for axis in 0..3 {
+            sum[axis] += (other[axis] - coords[axis]) as i64;
+        }
+    }
+    let n = matches.len() as f64;
+    let mean = sum.map(|s| (s as f64 / n).round() as i32);
+    add_coords(position, mean)
+}
+
+/// Beacons of all the scanners, merging those within the tolerance
+fn map_beacons(placements: &[Placement], tolerance: i32) -> Vec<MapBeacon> {
+    let mut grid: Grid<usize> = Grid::new(tolerance);
+    let mut beacons: Vec<MapBeacon> = Vec::new();
+    for placement in placements {
+        for &coords in &placement.beacons {
+            let near = grid.near(coords).next().map(|&(_, i)| i);
+            match near {
+                Some(i) => {
+                    let scanners = &mut beacons[i].scanners;
+                    if !scanners.contains(&placement.scanner) {
+                        scanners.push(placement.scanner);
+                    }
+                },
+                None => {
+                    grid.insert(coords, beacons.len());
+                    beacons.push(MapBeacon { coords, scanners: vec![placement.scanner] });
+                },
+            }
+        }
+    }
+    beacons.sort_unstable_by_key(|beacon| beacon.coords);
+    beacons
+}
+
+/// Rows: scanner,id,x,y,z,,rotation matrix by rows and
+/// beacon,index,x,y,z,ids of scanners that saw it, separated by spaces
+fn write_csv<W: Write>(mut f: W, placements: &[Placement], beacons: &[MapBeacon]) -> io::Result<()> {
+    writeln!(f, "type,id,x,y,z,scanners,rotation")?;
+    for placement in placements {
+        let [x, y, z] = placement.position;
+        let matrix: Vec<String> = placement.rotation.0.iter().flatten().map(|n| n.to_string()).collect();
+        writeln!(f, "scanner,{},{},{},{},,{}", placement.scanner, x, y, z, matrix.join(" "))?;
+    }
+    for (i, beacon) in beacons.iter().enumerate() {
+        let [x, y, z] = beacon.coords;
+        let scanners: Vec<String> = beacon.scanners.iter().map(|id| id.to_string()).collect();
+        writeln!(f, "beacon,{},{},{},{},{},", i, x, y, z, scanners.join(" "))?;
+    }
+    f.flush()
+}
+
+/// ASCII PLY point cloud, with beacons in white and scanners in red
+fn write_ply<W: Write>(mut f: W, placements: &[Placement], beacons: &[MapBeacon]) -> io::Result<()> {
+    writeln!(f, "ply")?;
+    writeln!(f, "format ascii 1.0")?;
+    writeln!(f, "element vertex {}", placements.len() + beacons.len())?;
+    for property in ["float x", "float y", "float z", "uchar red", "uchar green", "uchar blue"] {
+        writeln!(f, "property {}", property)?;
+    }
+    writeln!(f, "end_header")?;
+    for placement in placements {
+        let [x, y, z] = placement.position;
+        writeln!(f, "{} {} {} 255 0 0", x, y, z)?;
+    }
+    for beacon in beacons {
+        let [x, y, z] = beacon.coords;
+        writeln!(f, "{} {} {} 255 255 255", x, y, z)?;
+    }
+    f.flush()
+}
+
+impl fmt::Display for AlignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let orphans: Vec<String> = self.orphans.iter().map(|i| i.to_string()).collect();
+        write!(f, "Scanners {} can't be aligned", orphans.join(", "))
+    }
+}
+
+impl Matrix {
+    const IDENTITY: Matrix = Matrix([[1, 0, 0], [0, 1, 0], [0, 0, 1]]);
+
+    fn apply(&self, coords: Coords) -> Coords {
+        self.0.map(|row| row[0] * coords[0] + row[1] * coords[1] + row[2] * coords[2])
+    }
+
+    fn mul(&self, other: &Matrix) -> Matrix {
+        let m = &self.0;
+        let columns = other.0;
+        Matrix([0, 1, 2].map(|i| [0, 1, 2].map(|j| (0..3).map(|k| m[i][k] * columns[k][j]).sum())))
+    }
+
+    fn transpose(&self) -> Matrix {
+        Matrix([0, 1, 2].map(|i| [0, 1, 2].map(|j| self.0[j][i])))
+    }
+
+    fn determinant(&self) -> i32 {
+        let m = &self.0;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+}
+
+/// The 24 rotations that keep the axes aligned, composing 90 degrees
+/// rotations around X and Y axes until no new ones appear. Identity is the
+/// 1st one.
+fn calculate_all_rotations() -> Vec<Matrix> {
+    let generators = [
+        Matrix([[1, 0, 0], [0, 0, -1], [0, 1, 0]]),
+        Matrix([[0, 0, 1], [0, 1, 0], [-1, 0, 0]]),
+    ];
+
+    let mut all_rotations = vec![Matrix::IDENTITY];
+    let mut i = 0;
+    while i < all_rotations.len() {
+        for generator in &generators {
+            let m = all_rotations[i].mul(generator);
+            if !all_rotations.contains(&m) {
+                all_rotations.push(m);
             }
         }
+        i += 1;
     }
-    matches >= 12
+
+    // Proper rotations: orthogonal and not mirroring
+    debug_assert!(all_rotations.iter().all(|m| m.determinant() == 1 && m.mul(&m.transpose()) == Matrix::IDENTITY));
+    debug_assert_eq!(all_rotations.len(), 24);
+    all_rotations
 }
 
 fn sub_coords(c1: Coords, c2: Coords) -> Coords {
     [c1[0] - c2[0], c1[1] - c2[1], c1[2] - c2[2]]
 }
 
+fn add_coords(c1: Coords, c2: Coords) -> Coords {
+    [c1[0] + c2[0], c1[1] + c2[1], c1[2] + c2[2]]
+}
+
 fn parse_scanner_beacons<T>(mut lines: T) -> Option<Vec<Coords>>
 where
     T: Iterator<Item = String>
 {
-    if let None = lines.next() {
-        return None;
-    }
+    lines.next()?;
 
     let mut coords_list: Vec<Coords> = Vec::new();
-    while let Some(line) = lines.next() {
+    for line in lines {
         if line.trim() == "" {
             break;
         }
@@ -150,3 +476,163 @@ where
     }
     Some(coords_list)
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Scanners at the given positions and rotations, seeing the beacons of a
+    /// random cloud within 1000 units, with readings off by up to noise
+    fn make_scanners(seed: u64, scanners: &[(Coords, usize)], noise: i32) -> (Vec<Coords>, Vec<Scanner>) {
+        let mut state = seed;
+        let mut next = move |n: i32| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n as u64) as i32
+        };
+        let cloud: Vec<Coords> = (0..400).map(|_| [next(3000) - 1000, next(3000) - 1000, next(3000) - 1000]).collect();
+
+        let rotations = calculate_all_rotations();
+        let scanners = scanners.iter()
+            .map(|&(position, rotation)| {
+                let inverse = rotations[rotation].transpose();
+                let beacons = cloud.iter()
+                    .map(|&coords| sub_coords(coords, position))
+                    .filter(|diff| diff.iter().all(|d| d.abs() <= 1000))
+                    .map(|diff| inverse.apply(diff).map(|c| c + next(2 * noise + 1) - noise))
+                    .collect();
+                Scanner::new(beacons)
+            })
+            .collect();
+        (cloud, scanners)
+    }
+
+    const POSITIONS: [(Coords, usize); 4] = [([0, 0, 0], 0), ([900, 100, -50], 5), ([-200, 1000, 300], 17), ([1800, 0, 0], 23)];
+
+    #[test]
+    fn rotations() {
+        let rotations = calculate_all_rotations();
+        let set: HashSet<Matrix> = rotations.iter().copied().collect();
+        assert_eq!(rotations.len(), 24);
+        assert_eq!(set.len(), 24);
+        assert_eq!(rotations[0], Matrix::IDENTITY);
+        let rotated: HashSet<Coords> = rotations.iter().map(|r| r.apply([1, 2, 3])).collect();
+        assert_eq!(rotated.len(), 24);
+
+        // A group: closed under composition, with inverses, and each one is
+        // a proper rotation (orthogonal, determinant 1)
+        for a in &rotations {
+            assert_eq!(a.determinant(), 1);
+            assert_eq!(a.mul(&a.transpose()), Matrix::IDENTITY);
+            assert!(set.contains(&a.transpose()));
+            for b in &rotations {
+                let ab = a.mul(b);
+                assert!(set.contains(&ab));
+                assert_eq!(ab.apply([1, 2, 3]), a.apply(b.apply([1, 2, 3])));
+            }
+        }
+
+        // They are all the matrices with a 1 or -1 in each row and column
+        // that don't mirror
+        let mut expected = HashSet::new();
+        for axes in [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]] {
+            for signs in 0..8 {
+                let mut m = [[0; 3]; 3];
+                for row in 0..3 {
+                    m[row][axes[row]] = if signs & (1 << row) == 0 { 1 } else { -1 };
+                }
+                if Matrix(m).determinant() == 1 {
+                    expected.insert(Matrix(m));
+                }
+            }
+        }
+        assert_eq!(set, expected);
+    }
+
+    #[test]
+    fn align_test() {
+        let (cloud, scanners) = make_scanners(7, &POSITIONS, 0);
+        let placements = align(&scanners, &AlignOptions::default()).unwrap();
+        let rotations = calculate_all_rotations();
+        for (placement, &(position, rotation)) in placements.iter().zip(&POSITIONS) {
+            assert_eq!(placement.position, position);
+            assert_eq!(placement.rotation, rotations[rotation]);
+            assert!(placement.beacons.iter().all(|coords| cloud.contains(coords)));
+        }
+
+        for beacon in map_beacons(&placements, 0) {
+            let expected: Vec<usize> = POSITIONS.iter()
+                .enumerate()
+                .filter(|(_, (position, _))| sub_coords(beacon.coords, *position).iter().all(|d| d.abs() <= 1000))
+                .map(|(i, _)| i)
+                .collect();
+            let mut scanners = beacon.scanners.clone();
+            scanners.sort_unstable();
+            assert_eq!(scanners, expected);
+        }
+    }
+
+    #[test]
+    fn export() {
+        let rotation = Matrix([[1, 0, 0], [0, 0, -1], [0, 1, 0]]);
+        let placements = [
+            Placement { scanner: 0, position: [0, 0, 0], rotation: Matrix::IDENTITY, beacons: vec![[1, 2, 3]] },
+            Placement { scanner: 1, position: [10, 0, -5], rotation, beacons: vec![[1, 2, 3], [4, 5, 6]] },
+        ];
+        let beacons = map_beacons(&placements, 0);
+        assert_eq!(beacons, [
+            MapBeacon { coords: [1, 2, 3], scanners: vec![0, 1] },
+            MapBeacon { coords: [4, 5, 6], scanners: vec![1] },
+        ]);
+
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &placements, &beacons).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "\
+type,id,x,y,z,scanners,rotation
+scanner,0,0,0,0,,1 0 0 0 1 0 0 0 1
+scanner,1,10,0,-5,,1 0 0 0 0 -1 0 1 0
+beacon,0,1,2,3,0 1,
+beacon,1,4,5,6,1,
+");
+
+        let mut ply = Vec::new();
+        write_ply(&mut ply, &placements, &beacons).unwrap();
+        let ply = String::from_utf8(ply).unwrap();
+        assert!(ply.starts_with("ply\nformat ascii 1.0\nelement vertex 4\n"));
+        assert!(ply.ends_with("end_header\n0 0 0 255 0 0\n10 0 -5 255 0 0\n1 2 3 255 255 255\n4 5 6 255 255 255\n"));
+    }
+
+    #[test]
+    fn noisy_align() {
+        let (cloud, scanners) = make_scanners(11, &POSITIONS, 1);
+        assert!(align(&scanners, &AlignOptions::default()).is_err());
+
+        let options = AlignOptions { tolerance: 2, ..AlignOptions::default() };
+        let placements = align(&scanners, &options).unwrap();
+        for (placement, &(position, _)) in placements.iter().zip(&POSITIONS) {
+            assert!(sub_coords(placement.position, position).iter().all(|d| d.abs() <= 2));
+        }
+        let seen: HashSet<Coords> = cloud.iter()
+            .copied()
+            .filter(|coords| POSITIONS.iter().any(|(position, _)| sub_coords(*coords, *position).iter().all(|d| d.abs() <= 1000)))
+            .collect();
+        assert_eq!(map_beacons(&placements, options.tolerance).len(), seen.len());
+    }
+
+    #[test]
+    fn orphans() {
+        let mut positions = POSITIONS.to_vec();
+        positions.insert(2, ([10000, 0, 0], 3));
+        let (_, scanners) = make_scanners(7, &positions, 0);
+        let e = align(&scanners, &AlignOptions::default()).unwrap_err();
+        assert_eq!(e.orphans, [2]);
+        assert_eq!(e.to_string(), "Scanners 2 can't be aligned");
+        assert_eq!(e.partial.iter().filter(|p| p.is_some()).count(), 4);
+
+        let options = AlignOptions { min_overlap: 500, ..AlignOptions::default() };
+        assert_eq!(align(&scanners, &options).unwrap_err().orphans, [1, 2, 3, 4]);
+    }
+}
@@ -1,16 +1,65 @@
+use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::thread;
+use std::time::Instant;
 
+/// Image with the pixels of each row packed in bits, 64 by word. The pixels
+/// out of the image, up to infinity, are bg.
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct Img {
-    img: Vec<Vec<u8>>,
+    rows: Vec<Vec<u64>>,
+    width: usize,
     bg: u8,
 }
 
+/// Lit pixels count, infinite when the background is lit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LitCount {
+    Finite(u64),
+    Infinite,
+}
+
+/// How the infinite background changes with the enhancements
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Background {
+    AlwaysDark,
+    /// Lit after odd passes and dark after even ones
+    Flipping,
+    /// Lit after any pass
+    AlwaysLit,
+}
+
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "bench") {
+        let size = args.get(1).map_or(1000, |s| s.parse().expect("Invalid size"));
+        let passes = args.get(2).map_or(50, |s| s.parse().expect("Invalid passes"));
+        bench(size, passes);
+        return;
+    }
+
+    let passes = parse_args(&args).unwrap_or_else(|e| panic!("{}", e));
+
     let (filter, mut img) = parse_input("input.txt");
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    let background = background(&filter);
+    if let Some(explanation) = background.explanation() {
+        println!("{}", explanation);
+    }
+
+    if let Some(passes) = passes {
+        for _ in 0..passes {
+            img = get_filtered_img(&filter, &img, threads);
+        }
+        println!("{} passes: px lit count={}", passes, count_lit(&img));
+        return;
+    }
 
     for i in 1..=50 {
-        img = get_filtered_img(&filter, &img);
+        img = get_filtered_img(&filter, &img, threads);
         if i == 2 {
             println!("Part 1: px lit count={}", count_lit(&img));
         }
@@ -19,21 +68,170 @@ fn main() {
     println!("Part 2: px lit count={}", count_lit(&img));
 }
 
-fn get_filtered_img(filter: &Vec<u8>, img: &Img) -> Img {
-    let rows = img.img.len() + 2;
-    let cols = img.img[0].len() + 2;
-
-    let mut new_img = Vec::with_capacity(rows);
-    for i in 0..rows {
-        let mut px_row = Vec::with_capacity(cols);
-        for j in 0..cols {
-            let i = i as isize - 1;  // indexes in orig img are (i - 1, j - 1)
-            let j = j as isize - 1;
-            let filter_idx = get_filter_idx(img, (i, j));
-            let px = filter[filter_idx];
-            px_row.push(px);
+/// Options:
+///   --passes N                   count the lit pxs after N enhancements
+///                                instead of solving the puzzle
+fn parse_args(args: &[String]) -> Result<Option<usize>, String> {
+    let mut passes = None;
+
+    let mut args = args.iter();
+    while let Some(opt) = args.next() {
+        let value = args.next().ok_or(format!("Missing value for option '{}'", opt))?;
+        match opt.as_str() {
+            "--passes" => passes = Some(value.parse().map_err(|_| format!("Invalid passes '{}'", value))?),
+            _ => return Err(format!("Invalid option '{} {}'", opt, value)),
+        }
+    }
+
+    Ok(passes)
+}
+
+/// The background is dark at the start. If the algorithm lights a dark 3x3
+/// block, it's lit after the 1st pass, and then it depends on what the
+/// algorithm does with a lit 3x3 block.
+fn background(filter: &[u8]) -> Background {
+    match (filter[0], filter[0b111111111]) {
+        (0, _) => Background::AlwaysDark,
+        (_, 0) => Background::Flipping,
+        _ => Background::AlwaysLit,
+    }
+}
+
+impl Background {
+    fn explanation(&self) -> Option<&'static str> {
+        match self {
+            Background::AlwaysDark => None,
+            Background::Flipping => Some(
+                "The algorithm lights the pxs with all the pxs around dark (1st char is #) and darkens the pxs \
+                 with all the pxs around lit (last char is .), so the infinite background flips each pass: \
+                 infinite pxs are lit after odd passes and the count is only finite after even passes"),
+            Background::AlwaysLit => Some(
+                "The algorithm lights the pxs with all the pxs around dark (1st char is #) and keeps lit the pxs \
+                 with all the pxs around lit (last char is #), so after any pass the infinite background is lit \
+                 and the lit pxs count is infinite"),
+        }
+    }
+}
+
+/// Enhance a random square image, with 1 thread and with all of them
+fn bench(size: usize, passes: usize) {
+    let mut state = 0x2545f4914f6cdd1d_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    // Lit pxs never turn on the background
+    let filter: Vec<u8> = (0..512).map(|i| if i == 0 { 0 } else { (next() & 1) as u8 }).collect();
+    let pixels: Vec<Vec<u8>> = (0..size)
+        .map(|_| (0..size).map(|_| (next() & 1) as u8).collect())
+        .collect();
+    let img = Img::from_pixels(&pixels, 0);
+
+    let max_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    for threads in [1, max_threads] {
+        let start = Instant::now();
+        let mut result = img.clone();
+        for _ in 0..passes {
+            result = get_filtered_img(&filter, &result, threads);
+        }
+        println!("{}x{} image, {} passes, {} threads: {:?}, {} px lit",
+                 size, size, passes, threads, start.elapsed(), count_lit(&result));
+        if threads == max_threads {
+            break;
+        }
+    }
+}
+
+impl Img {
+    fn from_pixels(pixels: &[Vec<u8>], bg: u8) -> Self {
+        let width = pixels.first().map_or(0, |row| row.len());
+        let rows = pixels.iter()
+            .map(|row| {
+                let mut bits = vec![0; width.div_ceil(64)];
+                for (j, &px) in row.iter().enumerate() {
+                    bits[j / 64] |= (px as u64) << (j % 64);
+                }
+                bits
+            })
+            .collect();
+        Img { rows, width, bg }
+    }
+
+    fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Pixel of a row, that may be out of the image
+    fn row_px(&self, row: Option<&[u64]>, j: isize) -> u8 {
+        match row {
+            Some(row) if j >= 0 && (j as usize) < self.width => ((row[j as usize / 64] >> (j % 64)) & 1) as u8,
+            _ => self.bg,
+        }
+    }
+
+    fn row(&self, i: isize) -> Option<&[u64]> {
+        usize::try_from(i).ok().and_then(|i| self.rows.get(i)).map(|row| row.as_slice())
+    }
+
+    /// Filter index for the pixel, from the 3x3 pixels around it
+    fn filter_idx(&self, i: isize, j: isize) -> usize {
+        let mut filter_idx = 0;
+        for i in i - 1..=i + 1 {
+            let row = self.row(i);
+            for j in j - 1..=j + 1 {
+                filter_idx = (filter_idx << 1) | self.row_px(row, j) as usize;
+            }
+        }
+        filter_idx
+    }
+
+    /// Enhanced output row i of an image 1 px bigger by each side, so its
+    /// pixel j is pixel j - 1 of row i - 1 here. The filter index is rolled
+    /// along the row: the left column is dropped and the right one added.
+    fn filtered_row(&self, filter: &[u8], i: usize) -> Vec<u64> {
+        let width = self.width + 2;
+        let i = i as isize - 1;
+        let rows = [self.row(i - 1), self.row(i), self.row(i + 1)];
+
+        let mut bits = vec![0; width.div_ceil(64)];
+        let mut filter_idx = self.filter_idx(i, -1);
+        for j in 0..width {
+            if j > 0 {
+                let col = j as isize;
+                filter_idx = ((filter_idx << 1) & 0b110_110_110)
+                    | (self.row_px(rows[0], col) as usize) << 6
+                    | (self.row_px(rows[1], col) as usize) << 3
+                    | self.row_px(rows[2], col) as usize;
+            }
+            bits[j / 64] |= (filter[filter_idx] as u64) << (j % 64);
+        }
+        bits
+    }
+}
+
+/// Enhance the image, splitting the rows between threads
+fn get_filtered_img(filter: &[u8], img: &Img, threads: usize) -> Img {
+    let height = img.height() + 2;
+    let threads = threads.clamp(1, height);
+
+    let mut rows = vec![Vec::new(); height];
+    if threads == 1 {
+        for (i, row) in rows.iter_mut().enumerate() {
+            *row = img.filtered_row(filter, i);
         }
-        new_img.push(px_row);
+    } else {
+        let chunk_len = height.div_ceil(threads);
+        thread::scope(|scope| {
+            for (chunk_idx, chunk) in rows.chunks_mut(chunk_len).enumerate() {
+                scope.spawn(move || {
+                    for (k, row) in chunk.iter_mut().enumerate() {
+                        *row = img.filtered_row(filter, chunk_idx * chunk_len + k);
+                    }
+                });
+            }
+        });
     }
 
     let new_bg = if img.bg == 0 {
@@ -42,47 +240,35 @@ fn get_filtered_img(filter: &Vec<u8>, img: &Img) -> Img {
         filter[0b111111111]
     };
 
-    Img{ img: new_img, bg: new_bg }
+    Img { rows, width: img.width + 2, bg: new_bg }
 }
 
-fn get_filter_idx(img: &Img, (i, j): (isize, isize)) -> usize {
-    let mut shift = 8;
-    let mut filter_idx = 0;
-    for i in i - 1..=i + 1 {
-        for j in j - 1..=j + 1 {
-            let px = if valid_px(img, (i, j)) {
-                img.img[i as usize][j as usize]
-            } else {
-                img.bg
-            };
-            filter_idx |= (px as usize) << shift;
-            shift -= 1;
-        }
+fn count_lit(img: &Img) -> LitCount {
+    if img.bg != 0 {
+        return LitCount::Infinite;
     }
-    filter_idx
+    LitCount::Finite(img.rows.iter().flatten().map(|bits| bits.count_ones() as u64).sum())
 }
 
-fn valid_px(img: &Img, (i, j): (isize, isize)) -> bool {
-    let i_len = img.img.len() as isize;
-    let j_len = img.img[0].len() as isize;
-    i >= 0 && i < i_len && j >= 0 && j < j_len
-}
-
-fn count_lit(img: &Img) -> u32 {
-    assert!(img.bg == 0, "Infinite lit pxs");
-    img.img.iter().flatten().map(|px| *px as u32).sum()
+impl fmt::Display for LitCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LitCount::Finite(n) => write!(f, "{}", n),
+            LitCount::Infinite => write!(f, "infinite"),
+        }
+    }
 }
 
 fn parse_input(filename: &str) -> (Vec<u8>, Img) {
-    let f = File::open(filename).expect(&format!("Error opening {}", filename));
+    let f = File::open(filename).unwrap_or_else(|_| panic!("Error opening {}", filename));
     let reader = BufReader::new(f);
     let mut lines = reader.lines().map(|l| l.unwrap());
 
     let filter = parse_line(&lines.next().unwrap());
     lines.next().unwrap();
-    let img: Vec<_> = lines.map(|l| parse_line(&l)).collect();
+    let pixels: Vec<_> = lines.map(|l| parse_line(&l)).collect();
 
-    (filter, Img{ img, bg: 0 })
+    (filter, Img::from_pixels(&pixels, 0))
 }
 
 fn parse_line(line: &str) -> Vec<u8> {
@@ -99,7 +285,7 @@ mod tests {
     fn filter_px() {
         let (filter, img) = parse_input("input_test.txt");
 
-        let filter_idx = get_filter_idx(&img, (2, 2));
+        let filter_idx = img.filter_idx(2, 2);
         assert_eq!(filter_idx, 34, "Wrong filter idx calculation");
 
         let px = filter[filter_idx];
@@ -112,20 +298,71 @@ mod tests {
 
         let expect_img = ".##.##.\n#..#.#.\n##.#..#\n####..#\n.#..##.\n..##..#\n...#.#.";
         let expect_img = parse_img_str(expect_img);
-        let filtered_img = get_filtered_img(&filter, &img);
-        assert_eq!(filtered_img.img, expect_img);
-        assert_eq!(filtered_img.bg, 0);
+        let filtered_img = get_filtered_img(&filter, &img, 1);
+        assert_eq!(filtered_img, expect_img);
 
         let expect_img = ".......#.\n.#..#.#..\n#.#...###\n#...##.#.\n#.....#.#\n.#.#####.\n..#.#####\n...##.##.\n....###..";
         let expect_img = parse_img_str(expect_img);
-        let filtered_img = get_filtered_img(&filter, &filtered_img);
-        assert_eq!(filtered_img.img, expect_img);
-        assert_eq!(filtered_img.bg, 0);
+        let filtered_img = get_filtered_img(&filter, &filtered_img, 1);
+        assert_eq!(filtered_img, expect_img);
 
-        assert_eq!(count_lit(&filtered_img), 35);
+        assert_eq!(count_lit(&filtered_img), LitCount::Finite(35));
     }
 
-    fn parse_img_str(input: &str) -> Vec<Vec<u8>> {
-        input.lines().map(|l| parse_line(l)).collect()
+    #[test]
+    fn infinite_background() {
+        let (mut filter, img) = parse_input("input_test.txt");
+        assert_eq!(background(&filter), Background::AlwaysDark);
+        assert_eq!(background(&filter).explanation(), None);
+
+        filter[0] = 1;
+        filter[0b111111111] = 0;
+        assert_eq!(background(&filter), Background::Flipping);
+        let mut img = img;
+        for pass in 1..=4 {
+            img = get_filtered_img(&filter, &img, 1);
+            if pass % 2 == 1 {
+                assert_eq!(count_lit(&img), LitCount::Infinite);
+            } else {
+                assert!(matches!(count_lit(&img), LitCount::Finite(_)));
+            }
+        }
+        assert_eq!(LitCount::Infinite.to_string(), "infinite");
+        assert_eq!(LitCount::Finite(35).to_string(), "35");
+
+        filter[0b111111111] = 1;
+        assert_eq!(background(&filter), Background::AlwaysLit);
+        let img = get_filtered_img(&filter, &img, 1);
+        assert_eq!(count_lit(&get_filtered_img(&filter, &img, 1)), LitCount::Infinite);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn wide_img_threads() {
+        let (filter, _) = parse_input("input_test.txt");
+        // Rows crossing several words, with lit pxs at the words edges
+        let pixels: Vec<Vec<u8>> = (0..70)
+            .map(|i| (0..130).map(|j| ((i * 7 + j * 3) % 5 == 0 || j % 64 == 63) as u8).collect())
+            .collect();
+        let img = Img::from_pixels(&pixels, 0);
+
+        // Slow but obvious enhancement, pixel by pixel
+        let mut expected = pixels.clone();
+        let mut threaded = img.clone();
+        for _ in 0..5 {
+            let prev = Img::from_pixels(&expected, 0);
+            expected = (0..expected.len() + 2)
+                .map(|i| (0..expected[0].len() + 2)
+                    .map(|j| filter[prev.filter_idx(i as isize - 1, j as isize - 1)])
+                    .collect())
+                .collect();
+            threaded = get_filtered_img(&filter, &threaded, 4);
+        }
+        assert_eq!(threaded, Img::from_pixels(&expected, 0));
+        assert_eq!(get_filtered_img(&filter, &img, 3), get_filtered_img(&filter, &img, 1));
+    }
+
+    fn parse_img_str(input: &str) -> Img {
+        let pixels: Vec<Vec<u8>> = input.lines().map(parse_line).collect();
+        Img::from_pixels(&pixels, 0)
+    }
+}